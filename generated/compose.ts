@@ -0,0 +1,180 @@
+// Generated by compose-validatr, do not edit
+
+export type Compose = { version?: string, include?: Array<Include>, services: { [key in string]: Service }, networks?: { [key in string]: Network | null }, volumes?: { [key in string]: Volume | null }, configs?: { [key in string]: Config | null }, secrets?: { [key in string]: Secret | null }, };
+
+export type Service = { attach?: boolean, build?: Build, blkio_config?: BlkioConfig, cpu_count?: number, cpu_percent?: number, cpu_shares?: number, cpu_period?: string, cpu_quota?: string, cpu_rt_runtime?: string, cpu_rt_period?: string, cpus?: number, cpuset?: number, cap_add?: Array<Capabilities>, cap_drop?: Array<Capabilities>, cgroup?: Cgroup, cgroup_parent?: string, command?: Command, configs?: Array<ServiceConfig>, container_name?: string, credential_spec?: CredentialSpec, depends_on?: DependsOn, deploy?: Deploy, device_cgroup_rules?: Array<string>, devices?: Array<string>, dns?: Labels, dns_opt?: Array<string>, dns_search?: Labels, domainname?: string, entrypoint?: Labels, env_file?: Labels, environment?: Labels, expose?: Array<string>, extends?: Extends, annotations?: Labels, external_links?: Array<string>, extra_hosts?: Labels, group_add?: Array<string>, healthcheck?: HealthCheck, hostname?: string, image?: string, init?: boolean, ipc?: string, uts?: string, isolation?: string, labels?: Labels, links?: Array<string>, logging?: Logging, network_mode?: string, networks?: Networks, mac_address?: string, mem_limit?: string, mem_reservation?: string, mem_swappiness?: number, memswap_limit?: string, oom_kill_disable?: boolean, oom_score_adj?: number, pid?: string, pids_limit?: number, platform?: string, ports?: Array<Ports>, privileged?: boolean, profiles?: Array<string>, pull_policy?: PullPolicy, read_only?: boolean, restart?: Restart, runtime?: string, scale?: number, secrets?: Array<ServiceSecret>, security_opt?: Array<string>, shm_size?: string, stdin_open?: string, stop_grace_period?: string, stop_signal?: string, storage_opt?: string, sysctls?: Labels, tmpfs?: Tmpfs, tty?: string, ulimits?: Ulimits, user?: string, userns_mode?: string, volumes?: Array<Volumes>, volumes_from?: Array<string>, working_dir?: string, };
+
+export type Labels = Array<string> | { [key in string]: string };
+
+export type Tmpfs = string | Array<string>;
+
+export type ServiceConfig = string | ConfigDetails;
+
+export type ConfigDetails = { source: string, target?: string, uid?: string, gid?: string, mode?: string, };
+
+export type Cgroup = "host" | "private";
+
+export type Command = string | Array<string>;
+
+export type CredentialSpec = { file?: string, registry?: string, config?: string, };
+
+export type DependsOn = Array<string> | { [key in string]: DependsOnDetail };
+
+export type DependsOnDetail = { restart?: boolean, condition?: DependsOnCondition, required?: boolean, };
+
+export type DependsOnCondition = "service_started" | "service_healthy" | "service_completed_successfully";
+
+export type Extends = string | ExtendsDetails;
+
+export type ExtendsDetails = { file?: string, service: string, };
+
+export type PullPolicy = "always" | "never" | "missing" | "build";
+
+export type Restart = "no" | "always" | "onfailure" | "unlessstopped";
+
+export type Ulimits = { nproc: number, nofile: Nofile, };
+
+export type Nofile = { soft: number, hard: number, };
+
+export type Capabilities = "ALL" | "AUDIT_CONTROL" | "AUDIT_READ" | "AUDIT_WRITE" | "BLOCK_SUSPEND" | "BPF" | "CHECKPOINT_RESTORE" | "CHOWN" | "DAC_OVERRIDE" | "DAC_READ_SEARCH" | "FOWNER" | "FSETID" | "IPC_LOCK" | "IPC_OWNER" | "KILL" | "LEASE" | "LINUX_IMMUTABLE" | "MAC_ADMIN" | "MAC_OVERRIDE" | "MKNOD" | "NET_ADMIN" | "NET_BIND_SERVICE" | "NET_BROADCAST" | "NET_RAW" | "PERFMON" | "SETGID" | "SETFCAP" | "SETPCAP" | "SETUID" | "SYS_ADMIN" | "SYS_BOOT" | "SYS_CHROOT" | "SYS_MODULE" | "SYS_NICE" | "SYS_PACCT" | "SYS_PTRACE" | "SYS_RAWIO" | "SYS_RESOURCE" | "SYS_TIME" | "SYS_TTY_CONFIG" | "SYSLOG" | "WAKE_ALARM";
+
+export type Logging = { driver?: string, options?: Options, };
+
+export type Options = { syslog_address: string, };
+
+export type Ports = string | number | PortOptions;
+
+export type PortOptions = { target: number, host_ip?: string, 
+/**
+ * A port or a range of ports, which may be written as a number such as `8080`
+ */
+published?: string | number, protocol?: Protocol, mode?: PortMode, };
+
+export type Protocol = "udp" | "tcp" | "sctp";
+
+export type PortMode = "host" | "ingress";
+
+export type Deploy = { replicas?: number, endpoint_mode?: EndpointMode, labels?: Labels, mode?: DeployMode, placement?: Placement, resources?: Resources, restart_policy?: RestartPolicy, rollback_config?: RollbackConfig, update_config?: UpdateConfig, };
+
+export type Placement = { constraints?: Labels, preferences?: Labels, };
+
+export type EndpointMode = "vip" | "dnsrr";
+
+export type DeployMode = "global" | "replicated";
+
+export type Resources = { limits?: Limits, reservations?: Reservations, };
+
+export type Limits = { cpus?: string, memory?: string, pids?: number, devices?: Array<DriverCapabilities>, capabilities?: Array<DriverCapabilities>, };
+
+export type DriverCapabilities = "gpu" | "tpu";
+
+export type RestartPolicy = { condition?: RestartCondition, delay?: string, max_attempts?: number, window?: string, };
+
+export type RestartCondition = "none" | "on_failure" | "any";
+
+export type Reservations = { cpus: string, memory: string, };
+
+export type RollbackConfig = { parallelism?: number, delay?: string, failure_action?: FailureAction, monitor?: string, max_failure_ration?: string, order?: Order, };
+
+export type UpdateConfig = { parallelism?: number, delay?: string, failure_action?: FailureAction, monitor?: string, max_failure_ration?: string, order?: Order, };
+
+export type Order = "stop-first" | "start-first";
+
+export type FailureAction = "continue" | "pause";
+
+export type BlkioConfig = { weight: number, weight_device?: Array<WeightDevice>, device_read_bps?: Array<DeviceReadBps>, device_read_iops?: Array<DeviceReadIops>, device_write_bps?: Array<DeviceWriteBps>, device_write_iops?: Array<DeviceWriteIops>, };
+
+export type WeightDevice = { path: string, weight: number, };
+
+export type DeviceReadBps = { path: string, rate: Rate, };
+
+export type DeviceWriteBps = { path: string, rate: Rate, };
+
+export type DeviceReadIops = { path: string, rate: Rate, };
+
+export type DeviceWriteIops = { path: string, rate: Rate, };
+
+export type Rate = { "String": string } | { "Bytes": number };
+
+export type Build = string | BuildDetails;
+
+export type BuildDetails = { context?: string, dockerfile?: string, dockerfile_inline?: string, args?: BuildArgs, ssh?: SshArgs, cache_from?: Array<string>, cache_to?: Array<string>, additional_contexts?: AdditionalContexts, extra_hosts?: Array<string>, isolation?: string, privileged?: boolean, labels?: BuildLabels, no_cache?: boolean, pull?: boolean, shm_size?: ShmSize, target?: string, secrets?: Array<BuildSecret>, tags?: Array<string>, platforms?: Array<string>, };
+
+export type BuildArgs = Array<string> | { [key in string]: string };
+
+export type SshArgs = Array<string> | { [key in string]: string };
+
+export type AdditionalContexts = Array<string> | { [key in string]: string };
+
+export type BuildLabels = Array<string> | { [key in string]: string };
+
+export type ShmSize = { "String": string } | { "Bytes": number };
+
+export type BuildSecret = string | SecretDetails;
+
+export type SecretDetails = { source: string, target: string, uid: string, gid: string, mode: string, };
+
+export type Volumes = string | ShortVolumeOptions | LongVolumeOptions;
+
+export type ShortVolumeOptions = { volume: string, container_path: string, access_mode: string, };
+
+export type LongVolumeOptions = { type: VolumeType, 
+/**
+ * Not set for anonymous volumes
+ */
+source?: string, target: string, read_only?: boolean, bind?: Bind, volume?: VolumeOptions, tmpfs?: VolumeTmpfs, consistency?: string, };
+
+export type VolumeType = "volume" | "bind" | "tmpfs" | "npipe" | "cluster";
+
+export type Bind = { propagation?: string, create_host_path?: boolean, selinux?: string, };
+
+export type VolumeOptions = { nocopy: boolean, };
+
+export type VolumeTmpfs = { size: string, mode: string, };
+
+export type HealthCheck = { test?: Test, interval?: string, timeout?: string, retries?: number, start_period?: string, start_interval?: string, disable?: boolean, };
+
+export type Test = { "String": string } | { "List": Array<string> };
+
+export type ServiceSecret = string | SecretOptions;
+
+export type SecretOptions = { source: string, target?: string, uid?: string, gid?: string, mode?: string, };
+
+export type Networks = Array<string> | { [key in string]: NetworkOptions };
+
+export type NetworkOptions = { aliases?: Array<string>, ipv4_address?: string, ipv6_address?: string, link_local_ips?: Array<string>, priority?: number, };
+
+export type Network = { attachable?: boolean, ipam?: Ipam, driver?: Driver, driver_opts?: { [key in string]: string }, enable_ipv6?: boolean, external?: boolean, config?: Array<IpamConfig>, internal?: boolean, labels?: Labels, name?: string, options?: { [key in string]: string }, };
+
+export type Ipam = { driver?: Driver, config?: IpamConfig, options?: { [key in string]: string }, };
+
+export type Driver = "none" | "default" | "host" | "bridge";
+
+export type IpamConfig = { subnet?: string, ip_range?: string, gateway?: string, aux_addresses?: { [key in string]: string }, };
+
+export type Volume = { driver?: string, driver_opts?: DriverOpts, external?: boolean, labels?: Labels, name?: string, };
+
+export type DriverOpts = { type?: string, o?: string, device?: string, };
+
+export type Config = { file?: string, external?: boolean, name?: string, };
+
+export type Secret = { file?: string, environment?: string, external?: boolean, name?: string, };
+
+export type Include = string | IncludeDetails;
+
+export type IncludeDetails = { 
+/**
+ * Files merged in order into the included manifest
+ */
+path: Paths, 
+/**
+ * The directory that relative paths within the included files, such as build contexts
+ * and bind mounts, are relative to. Defaults to the directory of the first file.
+ */
+project_directory?: string, 
+/**
+ * Files with the variables used to interpolate the included manifest
+ */
+env_file?: Paths, };
+
+export type Paths = string | Array<string>;
@@ -1,87 +1,460 @@
 //! Compose fields and validation
 
-use std::{collections::HashMap, fmt::Display};
+use std::fmt::Display;
+use std::io;
+use std::sync::OnceLock;
+
+use indexmap::IndexMap;
 
 use crate::{
     configs::Config,
-    errors::{ValidationError, ValidationErrors},
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    extends,
+    extensions::{self, Extensions},
+    graph::{DependencyGraph, StartupPlan},
+    include,
+    interpolation::{self, VariableSource},
+    loader::FileLoader,
+    merge, merge_keys,
     networks::Network,
+    render,
+    rules::Validator,
     secrets::Secret,
-    services::Service,
+    services::{ports, Service},
+    span::{pointer, Location, SourceMap, Span},
+    unknown_keys::{self, UnknownKeys},
     volumes::Volume,
 };
 
 use super::{configs, networks, secrets, services, volumes};
-use serde::{Deserialize, Serialize};
-use serde_yaml;
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_path_to_error::Segment;
+use serde_yaml::{self, Value};
 
 /// Represents an entire [Docker Compose](https://docs.docker.com/compose/compose-file/) manifest
 ///
 /// All fields other than the `services` field are optional. Optional fields are skipped
 /// from serialization if they are `None`
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Compose {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
 
-    pub services: HashMap<String, services::Service>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<include::Include>>,
+
+    pub services: IndexMap<String, services::Service>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub networks: Option<HashMap<String, Option<networks::Network>>>,
+    pub networks: Option<IndexMap<String, Option<networks::Network>>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub volumes: Option<HashMap<String, Option<volumes::Volume>>>,
+    pub volumes: Option<IndexMap<String, Option<volumes::Volume>>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub configs: Option<HashMap<String, Option<configs::Config>>>,
+    pub configs: Option<IndexMap<String, Option<configs::Config>>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub secrets: Option<HashMap<String, Option<secrets::Secret>>>,
+    pub secrets: Option<IndexMap<String, Option<secrets::Secret>>>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
+
+    /// Problems found while building the manifest that did not prevent it from being built,
+    /// such as deprecated fields, with [`Severity::Warning`] or [`Severity::Info`]
+    ///
+    /// [`Severity::Warning`]: crate::errors::Severity::Warning
+    /// [`Severity::Info`]: crate::errors::Severity::Info
+    #[serde(skip)]
+    pub warnings: Vec<ValidationError>,
 }
 
 impl Compose {
     /// Create and validate a [`Compose`] representation
+    ///
+    /// YAML merge keys such as `<<: *defaults` are resolved before the manifest is
+    /// deserialized. Every returned error carries the span of the offending node in `contents`
     pub fn new(contents: &str) -> Result<Self, ValidationErrors> {
+        Self::builder().document(contents).build()
+    }
+
+    /// Create and validate a [`Compose`] representation from a JSON document
+    ///
+    /// JSON documents go through the same validation as YAML documents, so the same manifest
+    /// produces the same errors in either format. Only the spans differ.
+    pub fn from_json(contents: &str) -> Result<Self, ValidationErrors> {
+        Self::builder().json(contents).build()
+    }
+
+    /// Read a YAML document from `reader`, see [`Compose::new`]
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, ValidationErrors> {
+        Self::new(&Self::read(reader)?)
+    }
+
+    /// Read a JSON document from `reader`, see [`Compose::from_json`]
+    pub fn from_json_reader(reader: impl io::Read) -> Result<Self, ValidationErrors> {
+        Self::from_json(&Self::read(reader)?)
+    }
+
+    /// Serialize to YAML, as with the `Display` implementation
+    pub fn to_writer(&self, writer: impl io::Write) -> Result<(), serde_yaml::Error> {
+        serde_yaml::to_writer(writer, self)
+    }
+
+    /// Serialize to pretty printed JSON
+    ///
+    /// Fails if an `x-` extension field holds a mapping with keys that are not strings.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Serialize to pretty printed JSON, see [`Compose::to_json`]
+    pub fn to_json_writer(&self, writer: impl io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    /// Render the services, the networks, named volumes, secrets and configs they use, and the
+    /// edges of their [`DependencyGraph`] as a Graphviz DOT digraph
+    ///
+    /// Dependency edges are labeled with their `depends_on` condition, or the field they come
+    /// from, and dashed when the dependency is optional.
+    pub fn to_dot(&self) -> String {
+        render::dot(self)
+    }
+
+    /// Render the same diagram as [`Compose::to_dot`] as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        render::mermaid(self)
+    }
+
+    /// Create and validate a [`Compose`] representation, interpolating variables first
+    ///
+    /// Variables such as `${TAG:-latest}` are resolved from `variables` before the manifest is
+    /// deserialized. Required variables that have no value are reported as
+    /// [`ErrorCode::MissingVariable`] errors.
+    pub fn with_variables(
+        contents: &str,
+        variables: &dyn VariableSource,
+    ) -> Result<Self, ValidationErrors> {
+        Self::builder()
+            .document(contents)
+            .variables(variables)
+            .build()
+    }
+
+    /// Deserialize the `x-` extension field `key` into `T`, returning `None` if it is not set
+    pub fn extension<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_yaml::Error> {
+        self.extensions.deserialize(key)
+    }
+
+    /// Create a [`ComposeBuilder`] to merge multiple documents, such as a `compose.yaml` and a
+    /// `compose.override.yaml`, into a single [`Compose`]
+    pub fn builder<'a>() -> ComposeBuilder<'a> {
+        ComposeBuilder::default()
+    }
+
+    /// The manifest with only the services that are enabled when `profiles` are selected
+    ///
+    /// Services without `profiles` are always enabled, others only when one of their profiles
+    /// is selected. `"*"` selects every profile, see [`Compose::with_all_profiles`]. An enabled
+    /// service that requires a disabled service through `depends_on` is reported as an
+    /// [`ErrorCode::DisabledDependency`] error.
+    pub fn with_profiles(&self, profiles: &[&str]) -> Result<Self, ValidationErrors> {
         let mut errors = ValidationErrors::new();
-        let compose: Result<Self, ValidationError> = serde_yaml::from_str(contents)
-            .map_err(|e| ValidationError::InvalidCompose(e.to_string()));
-
-        match compose {
-            Ok(c) => {
-                if let Some(networks) = &c.networks {
-                    Self::validate_networks(&c, networks, &mut errors);
-                };
-                if let Some(volumes) = &c.volumes {
-                    Self::validate_volumes(&c, volumes, &mut errors);
-                };
-                if let Some(configs) = &c.configs {
-                    Self::validate_configs(&c, configs, &mut errors);
-                };
-                if let Some(secrets) = &c.secrets {
-                    Self::validate_secrets(&c, secrets, &mut errors);
-                };
-                Self::validate_services(&c, &c.services, &mut errors);
-                if errors.has_errors() {
-                    return Err(errors);
+        let mut compose = self.clone();
+        compose
+            .services
+            .retain(|_, service| service.is_enabled(profiles));
+
+        for (name, service) in &compose.services {
+            let path = pointer(&pointer("/services", name), "depends_on");
+            for (token, dependency, required) in service.dependencies() {
+                let disabled = self
+                    .services
+                    .get(dependency)
+                    .is_some_and(|d| !d.is_enabled(profiles));
+                if disabled && required {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::DisabledDependency,
+                            &pointer(&path, token),
+                            format!("Depends on {dependency}, which is disabled by the selected profiles"),
+                        )
+                        .with_value(dependency),
+                    );
                 }
-                Ok(c)
             }
-            Err(err) => {
-                errors.add_error(err);
-                Err(errors)
+        }
+
+        if errors.has_errors() {
+            return Err(errors);
+        }
+        Ok(compose)
+    }
+
+    /// The relationships between the services, see [`DependencyGraph`]
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::new(self)
+    }
+
+    /// The order in which the services start and stop, in batches that can start in parallel
+    ///
+    /// Every relationship of the [`DependencyGraph`] orders the services, not only
+    /// `depends_on`. Plan the services enabled by a set of profiles with
+    /// [`Compose::with_profiles`] first: optional dependencies on disabled services are then
+    /// left out of the plan. Dependency cycles are reported as
+    /// [`ErrorCode::DependencyCycle`] errors.
+    ///
+    /// ```
+    /// use compose_validatr::Compose;
+    ///
+    /// let yaml = r#"
+    /// services:
+    ///   web:
+    ///     image: web
+    ///     depends_on:
+    ///       db:
+    ///         condition: service_healthy
+    ///   db:
+    ///     image: postgres
+    /// "#;
+    ///
+    /// let plan = Compose::new(yaml).unwrap().startup_plan().unwrap();
+    /// assert_eq!(plan.startup(), [vec!["db"], vec!["web"]]);
+    /// ```
+    pub fn startup_plan(&self) -> Result<StartupPlan, ValidationErrors> {
+        self.dependency_graph().startup_plan()
+    }
+
+    /// The manifest with every profile selected, which enables all services
+    pub fn with_all_profiles(&self) -> Self {
+        self.clone()
+    }
+
+    /// Convert every element that has a short and a long syntax to its long syntax
+    ///
+    /// Defaults that Compose applies are filled in, such as the `tcp` protocol of ports or the
+    /// `service_started` condition of `depends_on`, similar to the output of
+    /// `docker compose config`. Values that cannot be converted without the host, such as
+    /// environment variables without a value, are left as they are.
+    pub fn normalize(mut self) -> Self {
+        self.services.values_mut().for_each(Normalize::normalize);
+        for network in self
+            .networks
+            .iter_mut()
+            .flat_map(|n| n.values_mut())
+            .flatten()
+        {
+            if let Some(labels) = &mut network.labels {
+                labels.normalize();
+            }
+        }
+        for volume in self
+            .volumes
+            .iter_mut()
+            .flat_map(|v| v.values_mut())
+            .flatten()
+        {
+            if let Some(labels) = &mut volume.labels {
+                labels.normalize();
             }
         }
+        self
+    }
+
+    /// Inline `extends` in every service, loading other files through `loader`
+    ///
+    /// `extends` chains are followed to their end and each service is merged on top of its
+    /// base, so the returned manifest describes what will actually run. Circular chains are
+    /// reported as [`ErrorCode::InvalidExtends`] errors.
+    pub fn resolve_extends(&self, loader: &dyn FileLoader) -> Result<Self, ValidationErrors> {
+        let names: Vec<&str> = self.services.keys().map(String::as_str).collect();
+        let mut value = Self::to_value(self)?;
+        value["services"] = Value::Mapping(extends::resolve(
+            Self::to_value(&self.services)?
+                .as_mapping()
+                .cloned()
+                .unwrap_or_default(),
+            &names,
+            loader,
+        )?);
+        Self::from_value(&value, &SourceMap::default())?.validated(&SourceMap::default())
+    }
+
+    /// The effective service `name`, with its `extends` chain inlined
+    ///
+    /// See [`Compose::resolve_extends`].
+    pub fn resolve_service(
+        &self,
+        name: &str,
+        loader: &dyn FileLoader,
+    ) -> Result<Service, ValidationErrors> {
+        let services = Self::to_value(&self.services)?
+            .as_mapping()
+            .cloned()
+            .unwrap_or_default();
+        let resolved = extends::resolve(services, &[name], loader)?;
+        let path = pointer("/services", name);
+        // Round trip through text for the same leniency as `from_value`
+        let mut service: Service = serde_yaml::to_string(&resolved[name])
+            .and_then(|contents| serde_yaml::from_str(&contents))
+            .map_err(|e| {
+                ValidationErrors::from(ValidationError::new(
+                    ErrorCode::InvalidCompose,
+                    &path,
+                    e.to_string(),
+                ))
+            })?;
+        extensions::capture_service(&mut service, &resolved[name]);
+        Ok(service)
+    }
+
+    /// Keep the warnings in `problems`, or fail if any of them is an error
+    fn with_warnings(mut self, problems: ValidationErrors) -> Result<Self, ValidationErrors> {
+        if problems.has_errors() {
+            return Err(problems);
+        }
+        self.warnings.extend(problems);
+        Ok(self)
+    }
+
+    /// Convert to an untyped YAML value
+    fn to_value(value: &impl Serialize) -> Result<Value, ValidationErrors> {
+        serde_yaml::to_value(value)
+            .map_err(|e| ValidationError::new(ErrorCode::InvalidCompose, "", e.to_string()).into())
+    }
+
+    /// Parse `contents` into an untyped YAML value
+    pub(crate) fn parse_value(contents: &str) -> Result<Value, ValidationErrors> {
+        serde_yaml::from_str(contents).map_err(|e| {
+            let span = e.location().map(|l| Span::point(l.into()));
+            ValidationError::new(ErrorCode::InvalidCompose, "", e.to_string())
+                .with_span(span)
+                .into()
+        })
+    }
+
+    /// Parse JSON `contents` into an untyped YAML value
+    fn parse_json(contents: &str) -> Result<Value, ValidationErrors> {
+        let json: serde_json::Value = serde_json::from_str(contents).map_err(|e| {
+            // serde_json only reports the line and column, so count the offset from them
+            let offset = contents
+                .split_inclusive('\n')
+                .take(e.line().saturating_sub(1))
+                .map(str::len)
+                .sum::<usize>()
+                + e.column().saturating_sub(1);
+            let location = Location {
+                line: e.line(),
+                column: e.column(),
+                offset,
+            };
+            ValidationErrors::from(
+                ValidationError::new(ErrorCode::InvalidCompose, "", e.to_string())
+                    .with_span(Some(Span::point(location))),
+            )
+        })?;
+        Self::to_value(&json)
+    }
+
+    fn read(mut reader: impl io::Read) -> Result<String, ValidationErrors> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).map_err(|e| {
+            ValidationErrors::from(ValidationError::new(
+                ErrorCode::InvalidCompose,
+                "",
+                format!("Cannot read the document: {e}"),
+            ))
+        })?;
+        Ok(contents)
+    }
+
+    /// Deserialize a [`Compose`] from YAML text, keeping track of where deserialization failed
+    fn deserialize(contents: &str) -> Result<Self, ValidationErrors> {
+        static LOCATION: OnceLock<Regex> = OnceLock::new();
+        let deserializer = serde_yaml::Deserializer::from_str(contents);
+        serde_path_to_error::deserialize(deserializer).map_err(|e| {
+            let path = e
+                .path()
+                .iter()
+                .fold(String::new(), |path, segment| match segment {
+                    Segment::Seq { index } => pointer(&path, index),
+                    Segment::Map { key } => pointer(&path, key),
+                    _ => path,
+                });
+            let e = e.into_inner();
+            let span = e.location().map(|l| Span::point(l.into()));
+            // The location is already part of the span
+            let message = LOCATION
+                .get_or_init(|| Regex::new(r" at line \d+ column \d+$").unwrap())
+                .replace(&e.to_string(), "")
+                .into_owned();
+            ValidationError::new(ErrorCode::InvalidCompose, &path, message)
+                .with_span(span)
+                .into()
+        })
+    }
+
+    /// Deserialize a [`Compose`] from a YAML value that was produced by transforming a document
+    ///
+    /// Errors are located in `source` by their path, since their spans point into the
+    /// transformed document rather than the original source.
+    fn from_value(value: &Value, source: &SourceMap) -> Result<Self, ValidationErrors> {
+        // Deserializing from the text rather than the value keeps serde_yaml's leniency towards
+        // plain scalars, such as numbers in string fields
+        let contents = serde_yaml::to_string(value).map_err(|e| {
+            ValidationErrors::from(ValidationError::new(
+                ErrorCode::InvalidCompose,
+                "",
+                e.to_string(),
+            ))
+        })?;
+        let mut compose = Self::deserialize(&contents).map_err(|mut errors| {
+            errors.relocate(source);
+            errors
+        })?;
+        extensions::capture(&mut compose, value);
+        Ok(compose)
+    }
+
+    /// Validate a deserialized [`Compose`], locating any errors in `source`
+    fn validated(self, source: &SourceMap) -> Result<Self, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(networks) = &self.networks {
+            Self::validate_networks(&self, networks, &mut errors);
+        };
+        if let Some(volumes) = &self.volumes {
+            Self::validate_volumes(&self, volumes, &mut errors);
+        };
+        if let Some(configs) = &self.configs {
+            Self::validate_configs(&self, configs, &mut errors);
+        };
+        if let Some(secrets) = &self.secrets {
+            Self::validate_secrets(&self, secrets, &mut errors);
+        };
+        Self::validate_services(&self, &self.services, &mut errors);
+        errors.extend(self.dependency_graph().validate());
+        ports::validate_conflicts(&self, &mut errors);
+        errors.locate(source);
+        self.with_warnings(errors)
     }
 
     /// Validate top level networks
     fn validate_networks(
         compose: &Compose,
-        networks: &HashMap<String, Option<Network>>,
+        networks: &IndexMap<String, Option<Network>>,
         errors: &mut ValidationErrors,
     ) {
-        for (_, network_attributes) in networks {
+        for (name, network_attributes) in networks {
             if let Some(network) = network_attributes {
-                network.validate(compose, errors);
+                network.validate(compose, &pointer("/networks", name), errors);
             }
         }
     }
@@ -89,12 +462,12 @@ impl Compose {
     /// Validate top level volumes
     fn validate_volumes(
         compose: &Compose,
-        volumes: &HashMap<String, Option<Volume>>,
+        volumes: &IndexMap<String, Option<Volume>>,
         errors: &mut ValidationErrors,
     ) {
-        for (_, volume_attributes) in volumes {
+        for (name, volume_attributes) in volumes {
             if let Some(volume) = volume_attributes {
-                volume.validate(compose, errors);
+                volume.validate(compose, &pointer("/volumes", name), errors);
             }
         }
     }
@@ -102,12 +475,12 @@ impl Compose {
     /// Validate top level configs
     fn validate_configs(
         compose: &Compose,
-        configs: &HashMap<String, Option<Config>>,
+        configs: &IndexMap<String, Option<Config>>,
         errors: &mut ValidationErrors,
     ) {
-        for (_, config_attributes) in configs {
+        for (name, config_attributes) in configs {
             if let Some(config) = config_attributes {
-                config.validate(compose, errors);
+                config.validate(compose, &pointer("/configs", name), errors);
             }
         }
     }
@@ -115,12 +488,12 @@ impl Compose {
     /// Validate top level secrets
     fn validate_secrets(
         compose: &Compose,
-        secrets: &HashMap<String, Option<Secret>>,
+        secrets: &IndexMap<String, Option<Secret>>,
         errors: &mut ValidationErrors,
     ) {
-        for (_, secret_attributes) in secrets {
+        for (name, secret_attributes) in secrets {
             if let Some(secret) = secret_attributes {
-                secret.validate(compose, errors);
+                secret.validate(compose, &pointer("/secrets", name), errors);
             }
         }
     }
@@ -128,22 +501,210 @@ impl Compose {
     /// Validate services
     fn validate_services(
         compose: &Compose,
-        services: &HashMap<String, Service>,
+        services: &IndexMap<String, Service>,
         errors: &mut ValidationErrors,
     ) {
-        for (_, service) in services {
-            service.validate(compose, errors);
+        for (name, service) in services {
+            service.validate(compose, &pointer("/services", name), errors);
+        }
+    }
+}
+
+/// Builds a [`Compose`] from one or more documents
+///
+/// Documents are merged in the order they are added, following the Compose spec
+/// [merge rules](https://docs.docker.com/compose/compose-file/13-merge/): later documents
+/// override scalars, extend sequences and merge mappings. A later document can use the
+/// `!reset` tag to remove a value, or `!override` to replace it without merging.
+///
+/// Errors carry the index of the document they were found in as [`Span::document`].
+///
+/// ```
+/// use compose_validatr::Compose;
+///
+/// let base = "services:\n  web:\n    image: web:1.0\n";
+/// let dev = "services:\n  web:\n    image: web:dev\n";
+///
+/// let compose = Compose::builder().document(base).document(dev).build().unwrap();
+/// assert_eq!(compose.services["web"].image.as_deref(), Some("web:dev"));
+/// ```
+#[derive(Default)]
+pub struct ComposeBuilder<'a> {
+    documents: Vec<(&'a str, Format)>,
+    variables: Option<&'a dyn VariableSource>,
+    loader: Option<&'a dyn FileLoader>,
+    file_name: Option<&'a str>,
+    unknown_keys: UnknownKeys,
+    validator: Option<&'a Validator>,
+}
+
+impl<'a> ComposeBuilder<'a> {
+    /// Add a document, merged on top of the documents added before it
+    pub fn document(mut self, contents: &'a str) -> Self {
+        self.documents.push((contents, Format::Yaml));
+        self
+    }
+
+    /// Add a JSON document, merged on top of the documents added before it
+    pub fn json(mut self, contents: &'a str) -> Self {
+        self.documents.push((contents, Format::Json));
+        self
+    }
+
+    /// Interpolate variables in every document before merging
+    pub fn variables(mut self, variables: &'a dyn VariableSource) -> Self {
+        self.variables = Some(variables);
+        self
+    }
+
+    /// Load the files of the top level `include` element through `loader`
+    ///
+    /// Without a loader, `include` is kept as is but the included files are not loaded.
+    pub fn loader(mut self, loader: &'a dyn FileLoader) -> Self {
+        self.loader = Some(loader);
+        self
+    }
+
+    /// Name the file the documents were loaded from, as the loader would
+    ///
+    /// Included files are loaded relative to it, and those including it again are reported
+    /// as circular.
+    pub fn file_name(mut self, name: &'a str) -> Self {
+        self.file_name = Some(name);
+        self
+    }
+
+    /// Report keys that are not part of the model, which are ignored by default
+    ///
+    /// With [`UnknownKeys::Warn`], they end up in [`Compose::warnings`].
+    pub fn unknown_keys(mut self, mode: UnknownKeys) -> Self {
+        self.unknown_keys = mode;
+        self
+    }
+
+    /// Run the rules of `validator` instead of [`Validator::default`]
+    ///
+    /// Problems reported with [`Severity::Error`] fail the build, the others end up in
+    /// [`Compose::warnings`].
+    ///
+    /// [`Severity::Error`]: crate::errors::Severity::Error
+    pub fn validator(mut self, validator: &'a Validator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Merge and validate the documents
+    pub fn build(self) -> Result<Compose, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let mut maps = Vec::new();
+        let mut merged: Option<Value> = None;
+
+        for (index, (contents, format)) in self.documents.iter().enumerate() {
+            // JSON is a subset of YAML, so the YAML parser locates its nodes just as well
+            let source = SourceMap::for_document(contents, index);
+            let parsed = match format {
+                Format::Yaml => Compose::parse_value(contents),
+                Format::Json => Compose::parse_json(contents),
+            };
+            let mut value = match parsed {
+                Ok(value) => value,
+                Err(e) => {
+                    errors.extend(e.into_iter().map(|error| {
+                        let span = error.span.map(|s| s.in_document(index));
+                        error.with_span(span)
+                    }));
+                    continue;
+                }
+            };
+
+            let mut problems = ValidationErrors::new();
+            merge_keys::apply(&mut value, "", &mut problems);
+            if let Some(variables) = self.variables {
+                interpolation::interpolate(&mut value, variables, &source, "", &mut problems);
+            }
+            problems.locate(&source);
+            errors.extend(problems);
+
+            match &mut merged {
+                Some(base) => merge::merge(base, value),
+                None => merged = Some(value),
+            }
+            maps.push(source);
+        }
+
+        if errors.has_errors() {
+            return Err(errors);
         }
+        let Some(mut value) = merged else {
+            return Err(ValidationError::new(
+                ErrorCode::InvalidCompose,
+                "",
+                "No documents to merge",
+            )
+            .into());
+        };
+
+        let source = SourceMap::layered(maps);
+        if let Some(loader) = self.loader {
+            include::resolve(
+                &mut value,
+                self.file_name,
+                loader,
+                self.variables,
+                &mut errors,
+            );
+            if errors.has_errors() {
+                errors.locate(&source);
+                return Err(errors);
+            }
+        }
+        merge::strip_tags(&mut value);
+
+        #[cfg(feature = "compose-spec")]
+        let violations = crate::schema::validate(&value, &source);
+        let compose = Compose::from_value(&value, &source).and_then(|compose| {
+            let mut problems = ValidationErrors::new();
+            unknown_keys::check(&value, &compose, self.unknown_keys, &mut problems);
+            match self.validator {
+                Some(validator) => problems.extend(validator.check(&compose)),
+                None => problems.extend(Validator::default().check(&compose)),
+            }
+            problems.locate(&source);
+            match compose.validated(&source) {
+                Ok(compose) => compose.with_warnings(problems),
+                Err(mut errors) => {
+                    errors.extend(problems);
+                    Err(errors)
+                }
+            }
+        });
+        #[cfg(feature = "compose-spec")]
+        let compose = crate::schema::combine(violations, compose);
+        compose
     }
 }
 
+/// The format of a document added to a [`ComposeBuilder`]
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Yaml,
+    Json,
+}
+
 /// This trait needs to be implemented for top level elements
 pub(crate) trait Validate {
     /// Validate that an attribute is valid within the context of the compose manifest
     ///
     /// Push all validation errors to the ValidationErrors so that users are able to see
-    /// all of their errors at once, versus incrementally
-    fn validate(&self, ctx: &Compose, errors: &mut ValidationErrors);
+    /// all of their errors at once, versus incrementally. `path` is the JSON pointer style
+    /// path of `self` within the manifest, which is used to locate errors in the source.
+    fn validate(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors);
+}
+
+/// Implemented by elements that have both a short and a long syntax
+pub(crate) trait Normalize {
+    /// Convert to the long syntax, filling in the defaults Compose would apply
+    fn normalize(&mut self);
 }
 
 impl Display for Compose {
@@ -277,4 +838,243 @@ mod tests {
         let compose = Compose::new(yaml);
         assert!(compose.is_ok());
     }
+
+    #[test]
+    fn invalid_compose_span() {
+        let yaml = r#"
+        services:
+          gitlab:
+            image: gitlab/gitlab-ce:latest
+            cpu_count: lots
+        "#;
+
+        let compose = Compose::new(yaml);
+        let errors = compose.unwrap_err();
+        let span = errors.all_errors()[0].span.unwrap();
+        assert_eq!(span.start.line, 5);
+    }
+
+    #[test]
+    fn with_profiles() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            depends_on:
+              - db
+          db:
+            image: postgres
+            profiles: ["db"]
+          debug:
+            image: busybox
+            profiles: ["debug"]
+            depends_on:
+              web:
+                condition: service_started
+              db:
+                condition: service_started
+                required: false
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let active = compose.with_profiles(&["db", "debug"]).unwrap();
+        assert_eq!(active.services.len(), 3);
+
+        let errors = compose.with_profiles(&["debug"]).unwrap_err();
+        assert_eq!(errors.all_errors().len(), 1);
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::DisabledDependency);
+        assert_eq!(error.path, "/services/web/depends_on/0");
+
+        assert_eq!(compose.with_profiles(&["*"]).unwrap().services.len(), 3);
+        assert_eq!(compose.with_all_profiles().services.len(), 3);
+    }
+
+    #[test]
+    fn invalid_profile_name() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            profiles: ["debug", "-bad"]
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::InvalidProfile);
+        assert_eq!(error.path, "/services/web/profiles/1");
+        assert_eq!(error.span.unwrap().start.line, 5);
+    }
+
+    #[test]
+    fn normalize() {
+        use crate::services::{
+            build::Build, networks::Networks, ports::Ports, volumes::Volumes, Config, DependsOn,
+            DependsOnCondition, Labels,
+        };
+
+        let yaml = r#"
+        services:
+          web:
+            build: ./web
+            depends_on:
+              - db
+            environment:
+              - MODE=production
+            networks:
+              - backend
+            ports:
+              - "8080:80"
+            volumes:
+              - ./static:/srv/static:ro
+              - data:/data
+            configs:
+              - app
+          db:
+            image: postgres
+        networks:
+          backend:
+        volumes:
+          data:
+        configs:
+          app:
+            file: ./app.conf
+        "#;
+
+        let compose = Compose::new(yaml).unwrap().normalize();
+        let web = &compose.services["web"];
+        let Some(Build::Map(build)) = &web.build else {
+            panic!("build should be normalized");
+        };
+        assert_eq!(build.dockerfile.as_deref(), Some("Dockerfile"));
+        let Some(DependsOn::Map(depends_on)) = &web.depends_on else {
+            panic!("depends_on should be normalized");
+        };
+        assert_eq!(
+            depends_on["db"].condition,
+            Some(DependsOnCondition::ServiceStarted)
+        );
+        assert!(matches!(&web.environment, Some(Labels::Map(e)) if e["MODE"] == "production"));
+        assert!(matches!(&web.networks, Some(Networks::Map(n)) if n.contains_key("backend")));
+        let Some(Ports::Long(port)) = web.ports.as_ref().map(|p| &p[0]) else {
+            panic!("ports should be normalized");
+        };
+        assert_eq!(port.published.as_deref(), Some("8080"));
+        let volumes = web.volumes.as_ref().unwrap();
+        let Volumes::Long(bind) = &volumes[0] else {
+            panic!("volumes should be normalized");
+        };
+        assert_eq!(bind.read_only, Some(true));
+        assert!(matches!(&volumes[1], Volumes::Long(v) if v.source.as_deref() == Some("data")));
+        assert!(
+            matches!(&web.configs.as_ref().unwrap()[0], Config::Long(c) if c.target.as_deref() == Some("/app"))
+        );
+
+        // The canonical form is itself a valid manifest
+        let again = Compose::new(&compose.to_string()).unwrap().normalize();
+        assert_eq!(
+            serde_yaml::to_value(&again).unwrap(),
+            serde_yaml::to_value(&compose).unwrap()
+        );
+    }
+
+    #[test]
+    fn preserves_order() {
+        let yaml = r#"
+        services:
+          zookeeper:
+            image: zookeeper
+            environment:
+              ZOO_TICK_TIME: "2000"
+              ZOO_INIT_LIMIT: "5"
+          app:
+            image: app
+          kafka:
+            image: kafka
+        "#;
+
+        let mut compose = Compose::new(yaml).unwrap();
+        compose
+            .services
+            .insert("broker".to_owned(), compose.services["kafka"].clone());
+        let output = compose.to_string();
+        let positions: Vec<usize> = [
+            "zookeeper:",
+            "ZOO_TICK_TIME",
+            "ZOO_INIT_LIMIT",
+            "app:",
+            "kafka:",
+            "broker:",
+        ]
+        .iter()
+        .map(|key| output.find(key).unwrap())
+        .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{output}");
+    }
+
+    #[test]
+    fn json_errors_match_yaml() {
+        let manifests = [
+            "services:\n  web:\n    image: web\n    cpu_count: lots\n",
+            "services:\n  web:\n    image: web\n    container_name: -web\n    networks: [missing]\n",
+            "services:\n  web:\n    image: web\n    depends_on: [db]\n    expose: ['80', '99999']\n",
+            "services:\n  web:\n    image: web\n    profiles: ['-debug']\n    secrets: [token]\n",
+            "services:\n  web:\n    <<: [1]\n    image: web\n",
+        ];
+
+        for yaml in manifests {
+            let value: Value = serde_yaml::from_str(yaml).unwrap();
+            let json = serde_json::to_string_pretty(&value).unwrap();
+            let summary = |errors: ValidationErrors| -> Vec<_> {
+                errors
+                    .into_iter()
+                    .map(|e| (e.code, e.path, e.value, e.message, e.span.is_some()))
+                    .collect()
+            };
+
+            let expected = summary(Compose::new(yaml).unwrap_err());
+            assert!(!expected.is_empty());
+            assert_eq!(summary(Compose::from_json(&json).unwrap_err()), expected);
+            assert_eq!(
+                summary(Compose::from_json_reader(json.as_bytes()).unwrap_err()),
+                expected
+            );
+        }
+
+        let errors = Compose::from_json("{\"services\": {\n  \"web\": }}").unwrap_err();
+        let span = errors.all_errors()[0].span.unwrap();
+        assert_eq!(span.start.line, 2);
+        assert_eq!(span.start.offset, 15 + span.start.column - 1);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - "8080:80"
+            x-team: platform
+        "#;
+
+        let compose = Compose::from_reader(yaml.as_bytes()).unwrap();
+        let json = compose.to_json().unwrap();
+        let mut written = Vec::new();
+        compose.to_json_writer(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), json);
+
+        let compose = Compose::from_json(&json).unwrap();
+        assert_eq!(compose.services["web"].image.as_deref(), Some("web"));
+        assert_eq!(
+            compose.services["web"]
+                .extension::<String>("x-team")
+                .unwrap(),
+            Some("platform".to_owned())
+        );
+
+        let mut written = Vec::new();
+        compose.to_writer(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), compose.to_string());
+    }
 }
@@ -3,10 +3,12 @@
 use crate::compose::Compose;
 use serde::{Deserialize, Serialize};
 
-use crate::{compose::Validate, errors::ValidationErrors};
+use crate::{compose::Validate, errors::ValidationErrors, extensions::Extensions};
 
 /// Represents the top level [Config](https://docs.docker.com/compose/compose-file/08-configs/) element
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
@@ -16,13 +18,16 @@ pub struct Config {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 impl Validate for Config {
-    fn validate(&self, _: &Compose, _: &mut ValidationErrors) {
+    fn validate(&self, _: &Compose, _: &str, _: &mut ValidationErrors) {
         // Nothing to validate
         // Not interested in validating the existence of files on host
-        ()
     }
 }
 
@@ -41,8 +46,8 @@ mod tests {
 
         let configs: Config = serde_yaml::from_str(yaml).unwrap();
 
-        assert_eq!(configs.file.unwrap(), "path/to/config1".to_string());
-        assert_eq!(configs.external.unwrap(), true);
-        assert_eq!(configs.name.unwrap(), "named_config".to_string());
+        assert_eq!(configs.file.unwrap(), "path/to/config1".to_owned());
+        assert!(configs.external.unwrap());
+        assert_eq!(configs.name.unwrap(), "named_config".to_owned());
     }
 }
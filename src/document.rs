@@ -0,0 +1,710 @@
+//! Lossless editing of Compose files
+//!
+//! [`Compose`] is a typed model, so writing it back out with its `Display` implementation loses
+//! the comments, quoting and blank lines of the file it was read from. A [`Document`] keeps the
+//! original text instead, and applies every edit as a minimal [`Patch`] to that text.
+//!
+//! Each patch is checked by parsing the edited text again. Edits that cannot be written as a
+//! patch, such as changes to a node that is shared through an alias, are rejected with
+//! [`ErrorCode::InvalidEdit`] and leave the document unchanged.
+//!
+//! ```rust
+//! use compose_validatr::document::Document;
+//!
+//! let yaml = "services:\n  web:\n    image: \"nginx:1.25\" # pinned\n";
+//! let mut document = Document::parse(yaml).unwrap();
+//! document.set("/services/web/image", "nginx:1.27").unwrap();
+//! assert_eq!(
+//!     document.as_str(),
+//!     "services:\n  web:\n    image: \"nginx:1.27\" # pinned\n"
+//! );
+//! ```
+
+use std::ops::Range;
+
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    compose::Compose,
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    span::{key_token, pointer, tokens, Node, NodeKind, SourceMap},
+};
+
+/// A Compose file that is edited in place, keeping the text it was parsed from
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    value: Value,
+    map: SourceMap,
+}
+
+/// A textual change applied to a [`Document`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    /// Byte range of the replaced text, in the document as it was before the change
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Document {
+    /// Parse `contents`, which only needs to be valid YAML
+    pub fn parse(contents: &str) -> Result<Self, ValidationErrors> {
+        Ok(Document {
+            value: Compose::parse_value(contents)?,
+            map: SourceMap::for_document(contents, 0),
+            source: contents.to_owned(),
+        })
+    }
+
+    /// The current text of the document
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The node at the JSON pointer style `path`, e.g. `/services/web/image`
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        tokens(path)
+            .iter()
+            .try_fold(&self.value, |value, token| child(value, token))
+    }
+
+    /// Build and validate a [`Compose`] from the current text
+    pub fn compose(&self) -> Result<Compose, ValidationErrors> {
+        Compose::new(&self.source)
+    }
+
+    /// Set the node at `path` to `value`, creating any missing mappings along the way
+    ///
+    /// Replaced scalars keep their quoting style.
+    pub fn set(&mut self, path: &str, value: impl Serialize) -> Result<Patch, ValidationErrors> {
+        let value = to_value(path, value)?;
+        let mut expected = self.value.clone();
+        if !set_value(&mut expected, &tokens(path), value.clone()) {
+            return Err(invalid(path, "The parent of the node is not a mapping"));
+        }
+        let patch = self.set_patch(path, &value)?;
+        self.apply(patch, expected, path)
+    }
+
+    /// Append `item` to the sequence at `path`, creating the sequence if it is not set
+    pub fn push(&mut self, path: &str, item: impl Serialize) -> Result<Patch, ValidationErrors> {
+        let item = to_value(path, item)?;
+        let len = match self.get(path) {
+            Some(Value::Sequence(items)) if !items.is_empty() => items.len(),
+            None | Some(Value::Null) | Some(Value::Sequence(_)) => {
+                return self.set(path, [item]);
+            }
+            Some(_) => return Err(invalid(path, "The node is not a sequence")),
+        };
+
+        let mut expected = self.value.clone();
+        if let Some(Value::Sequence(items)) = get_mut(&mut expected, &tokens(path)) {
+            items.push(item.clone());
+        }
+        let patch = self.append(path, &item, len)?;
+        self.apply(patch, expected, path)
+    }
+
+    /// Remove the node at `path` from its parent mapping or sequence
+    pub fn remove(&mut self, path: &str) -> Result<Patch, ValidationErrors> {
+        let tokens = tokens(path);
+        let Some((token, parent_tokens)) = tokens.split_last() else {
+            return Err(invalid(path, "The document itself cannot be removed"));
+        };
+        let parent = parent(path);
+        let mut expected = self.value.clone();
+        let siblings = match get_mut(&mut expected, parent_tokens) {
+            Some(Value::Mapping(map)) => {
+                let key = map.keys().find(|k| key_token(k) == *token).cloned();
+                key.and_then(|k| map.shift_remove(k)).map(|_| map.len())
+            }
+            Some(Value::Sequence(items)) => match token.parse::<usize>() {
+                Ok(index) if index < items.len() => {
+                    items.remove(index);
+                    Some(items.len())
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(siblings) = siblings else {
+            return Err(invalid(path, "The node does not exist"));
+        };
+
+        let patch = if siblings == 0 {
+            // Removing the last entry leaves an empty collection, written in flow style
+            let empty = match self.get(parent) {
+                Some(Value::Sequence(_)) => Value::Sequence(Vec::new()),
+                _ => Value::Mapping(Mapping::new()),
+            };
+            self.replace(parent, &empty)?
+        } else {
+            self.cut(path)?
+        };
+        self.apply(patch, expected, path)
+    }
+
+    /// Apply `patch` if the edited text parses to `expected`
+    fn apply(
+        &mut self,
+        patch: Patch,
+        expected: Value,
+        path: &str,
+    ) -> Result<Patch, ValidationErrors> {
+        let mut source = self.source.clone();
+        source.replace_range(patch.range.clone(), &patch.replacement);
+        match Compose::parse_value(&source) {
+            Ok(value) if value == expected => {
+                self.map = SourceMap::for_document(&source, 0);
+                self.source = source;
+                self.value = value;
+                Ok(patch)
+            }
+            _ => Err(invalid(
+                path,
+                "The edit cannot be applied without rewriting the document",
+            )),
+        }
+    }
+
+    fn set_patch(&self, path: &str, value: &Value) -> Result<Patch, ValidationErrors> {
+        if self.get(path).is_some() {
+            return self.replace(path, value);
+        }
+
+        // Find the nearest ancestor that exists, and wrap the value in the missing mappings
+        let tokens = tokens(path);
+        let mut depth = tokens.len() - 1;
+        let mut ancestor = join(&tokens[..depth]);
+        while self.get(&ancestor).is_none() {
+            depth -= 1;
+            ancestor = join(&tokens[..depth]);
+        }
+        let wrapped = tokens[depth + 1..]
+            .iter()
+            .rev()
+            .fold(value.clone(), |value, key| {
+                Value::Mapping(Mapping::from_iter([(Value::String(key.clone()), value)]))
+            });
+
+        match self.get(&ancestor) {
+            Some(Value::Mapping(_)) => self.insert(&ancestor, &tokens[depth], &wrapped),
+            Some(Value::Null) => self.replace(
+                &ancestor,
+                &Value::Mapping(Mapping::from_iter([(
+                    Value::String(tokens[depth].clone()),
+                    wrapped,
+                )])),
+            ),
+            _ => Err(invalid(path, "The parent of the node is not a mapping")),
+        }
+    }
+
+    /// Replace the existing node at `path` with `value`
+    fn replace(&self, path: &str, value: &Value) -> Result<Patch, ValidationErrors> {
+        let node = *self.node(path)?;
+        let extent = self.extent(path)?;
+        let (quote, flow) = match node.kind {
+            NodeKind::Scalar { quote } => (quote, false),
+            NodeKind::Collection { flow } => (None, flow),
+        };
+        if flow || self.in_flow(path) {
+            return Ok(Patch {
+                range: extent,
+                replacement: flow_text(value, quote),
+            });
+        }
+
+        let value_start = match node.key {
+            Some(key) => self.colon(key.end.offset)? + 1,
+            None => extent.start,
+        };
+        if let Some(text) = inline(value, quote) {
+            return Ok(match node.kind {
+                NodeKind::Scalar { .. } if !extent.is_empty() => Patch {
+                    range: extent,
+                    replacement: text,
+                },
+                _ => Patch {
+                    range: value_start..extent.end,
+                    replacement: if node.key.is_some() {
+                        format!(" {text}")
+                    } else {
+                        text
+                    },
+                },
+            });
+        }
+
+        let indent = self.indent(extent.start);
+        let nested = match node.key {
+            Some(key) => self.indent(key.start.offset) + 2,
+            None => indent,
+        };
+        let block_collection = matches!(node.kind, NodeKind::Collection { .. });
+        if node.key.is_none() || (block_collection && (indent >= nested || value.is_sequence())) {
+            // Continue on the line the old value started on
+            Ok(Patch {
+                range: extent.clone(),
+                replacement: block(value, indent, path)?.trim_start().to_owned(),
+            })
+        } else {
+            Ok(Patch {
+                range: value_start..extent.end,
+                replacement: format!("\n{}", block(value, nested, path)?),
+            })
+        }
+    }
+
+    /// Add `key` with `value` to the existing mapping at `path`
+    fn insert(&self, path: &str, key: &str, value: &Value) -> Result<Patch, ValidationErrors> {
+        let key = inline(&Value::String(key.to_owned()), None).unwrap_or_default();
+        let extent = self.extent(path)?;
+        let children = self.children(path);
+
+        if matches!(self.node(path)?.kind, NodeKind::Collection { flow: true }) {
+            let entry = format!("{key}: {}", flow_text(value, None));
+            return Ok(match children.last() {
+                Some(last) => {
+                    let end = self.extent(last)?.end;
+                    Patch {
+                        range: end..end,
+                        replacement: format!(", {entry}"),
+                    }
+                }
+                None => Patch {
+                    range: extent.end - 1..extent.end - 1,
+                    replacement: entry,
+                },
+            });
+        }
+
+        let first_key = children
+            .first()
+            .and_then(|child| self.map.node(child)?.key)
+            .ok_or_else(|| invalid(path, "The mapping has no keys"))?;
+        let indent = self.indent(first_key.start.offset);
+        let entry = match inline(value, None) {
+            Some(text) => format!("{:indent$}{key}: {text}", ""),
+            None => format!("{:indent$}{key}:\n{}", "", block(value, indent + 2, path)?),
+        };
+        Ok(self.add_line(extent.end, entry))
+    }
+
+    /// Append `item` to the non-empty sequence at `path`, which has `len` items
+    fn append(&self, path: &str, item: &Value, len: usize) -> Result<Patch, ValidationErrors> {
+        let last = pointer(path, len - 1);
+        let end = self.extent(&last)?.end;
+        let quote = match self.node(&last)?.kind {
+            NodeKind::Scalar { quote } => quote,
+            NodeKind::Collection { .. } => None,
+        };
+
+        if matches!(self.node(path)?.kind, NodeKind::Collection { flow: true }) {
+            return Ok(Patch {
+                range: end..end,
+                replacement: format!(", {}", flow_text(item, quote)),
+            });
+        }
+
+        let dash = self.dash(&pointer(path, 0))?;
+        let indent = self.indent(dash);
+        let text = match inline(item, quote) {
+            Some(text) => text,
+            None => block(item, indent + 2, path)?.trim_start().to_owned(),
+        };
+        Ok(self.add_line(end, format!("{:indent$}- {text}", "")))
+    }
+
+    /// The patch that cuts the node at `path` out of its parent, which has other entries
+    fn cut(&self, path: &str) -> Result<Patch, ValidationErrors> {
+        let parent = parent(path);
+        let children = self.children(parent);
+        let index = children
+            .iter()
+            .position(|child| child == path)
+            .ok_or_else(|| invalid(path, "The node does not exist"))?;
+        let extent = self.extent(path)?;
+        let start = self.entry_start(path)?;
+
+        if self.in_flow(path) {
+            // Take the separating comma of the next entry, or else of the previous one
+            let range = match children.get(index + 1) {
+                Some(next) => start..self.entry_start(next)?,
+                None => self.extent(&children[index - 1])?.end..extent.end,
+            };
+            return Ok(Patch {
+                range,
+                replacement: String::new(),
+            });
+        }
+
+        let line_start = self.line_start(start);
+        let range = if self.source[line_start..start].trim().is_empty() {
+            line_start..self.line_end(extent.end)
+        } else {
+            // The first key of a mapping in a sequence shares its line with the dash
+            let next = children
+                .get(index + 1)
+                .ok_or_else(|| invalid(path, "The node cannot be removed"))?;
+            start..self.entry_start(next)?
+        };
+        Ok(Patch {
+            range,
+            replacement: String::new(),
+        })
+    }
+
+    fn node(&self, path: &str) -> Result<&Node, ValidationErrors> {
+        self.map
+            .node(path)
+            .ok_or_else(|| invalid(path, "The node is not written in the document"))
+    }
+
+    /// The paths of the entries of the mapping or sequence at `path`
+    fn children(&self, path: &str) -> Vec<String> {
+        match self.get(path) {
+            Some(Value::Mapping(map)) => map.keys().map(|k| pointer(path, key_token(k))).collect(),
+            Some(Value::Sequence(items)) => (0..items.len()).map(|i| pointer(path, i)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Byte range of the value at `path`, without trailing whitespace or comments
+    fn extent(&self, path: &str) -> Result<Range<usize>, ValidationErrors> {
+        let node = self.node(path)?;
+        let start = node.span.start.offset;
+        match node.kind {
+            NodeKind::Scalar { .. } if node.span.start == node.span.end => {
+                // An implicit null has no text of its own, so place it right after its key
+                let end = match node.key {
+                    Some(key) => self.colon(key.end.offset)? + 1,
+                    None => start,
+                };
+                Ok(end..end)
+            }
+            NodeKind::Scalar { .. } => {
+                let text = &self.source[start..node.span.end.offset];
+                Ok(start..start + text.trim_end().len())
+            }
+            NodeKind::Collection { flow } => {
+                let mut end = None;
+                for child in self.children(path) {
+                    end = end.max(Some(self.extent(&child)?.end));
+                }
+                if !flow {
+                    return end
+                        .map(|end| start..end)
+                        .ok_or_else(|| invalid(path, "The collection is empty"));
+                }
+                let from = end.unwrap_or(start + 1);
+                let close = self.source[from..]
+                    .find([']', '}'])
+                    .ok_or_else(|| invalid(path, "The collection is not closed"))?;
+                Ok(start..from + close + 1)
+            }
+        }
+    }
+
+    /// Where the entry for `path` starts, which is its key or its dash
+    fn entry_start(&self, path: &str) -> Result<usize, ValidationErrors> {
+        let node = self.node(path)?;
+        match node.key {
+            Some(key) => Ok(key.start.offset),
+            None if self.in_flow(path) => Ok(node.span.start.offset),
+            None => self.dash(path),
+        }
+    }
+
+    /// The offset of the dash in front of the sequence item at `path`
+    fn dash(&self, path: &str) -> Result<usize, ValidationErrors> {
+        let start = self.node(path)?.span.start.offset;
+        let before = self.source[..start].trim_end();
+        match before.strip_suffix('-') {
+            Some(rest) => Ok(rest.len()),
+            None => Err(invalid(path, "The sequence item has no dash")),
+        }
+    }
+
+    fn in_flow(&self, path: &str) -> bool {
+        !path.is_empty()
+            && matches!(
+                self.map.node(parent(path)).map(|n| n.kind),
+                Some(NodeKind::Collection { flow: true })
+            )
+    }
+
+    /// The offset of the colon following a key that ends at `offset`
+    fn colon(&self, offset: usize) -> Result<usize, ValidationErrors> {
+        self.source[offset..]
+            .find(':')
+            .map(|i| offset + i)
+            .ok_or_else(|| invalid("", "The key has no colon"))
+    }
+
+    fn line_start(&self, offset: usize) -> usize {
+        self.source[..offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// The offset just past the end of the line that contains `offset`
+    fn line_end(&self, offset: usize) -> usize {
+        self.source[offset..]
+            .find('\n')
+            .map_or(self.source.len(), |i| offset + i + 1)
+    }
+
+    fn indent(&self, offset: usize) -> usize {
+        offset - self.line_start(offset)
+    }
+
+    /// A patch adding `line` after the line that contains `offset`
+    fn add_line(&self, offset: usize, line: String) -> Patch {
+        let end = self.line_end(offset);
+        let replacement = match self.source[..end].ends_with('\n') {
+            true => format!("{line}\n"),
+            false => format!("\n{line}"),
+        };
+        Patch {
+            range: end..end,
+            replacement,
+        }
+    }
+}
+
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn invalid(path: &str, message: &str) -> ValidationErrors {
+    ValidationError::new(ErrorCode::InvalidEdit, path, message).into()
+}
+
+fn to_value(path: &str, value: impl Serialize) -> Result<Value, ValidationErrors> {
+    serde_yaml::to_value(value).map_err(|e| invalid(path, &e.to_string()))
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn join(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .fold(String::new(), |path, token| pointer(&path, token))
+}
+
+fn child<'a>(value: &'a Value, token: &str) -> Option<&'a Value> {
+    match value {
+        Value::Mapping(map) => map
+            .iter()
+            .find_map(|(k, v)| (key_token(k) == token).then_some(v)),
+        Value::Sequence(items) => items.get(token.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+fn get_mut<'a>(value: &'a mut Value, tokens: &[String]) -> Option<&'a mut Value> {
+    tokens.iter().try_fold(value, |value, token| match value {
+        Value::Mapping(map) => map
+            .iter_mut()
+            .find_map(|(k, v)| (key_token(k) == *token).then_some(v)),
+        Value::Sequence(items) => items.get_mut(token.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Set the node at `tokens` within `value`, creating missing mappings in place of nulls
+fn set_value(value: &mut Value, tokens: &[String], new: Value) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        *value = new;
+        return true;
+    };
+    if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+    }
+    if let Value::Mapping(map) = value {
+        if !map.keys().any(|k| key_token(k) == *token) {
+            map.insert(Value::String(token.clone()), Value::Null);
+        }
+    }
+    match get_mut(value, std::slice::from_ref(token)) {
+        Some(child) => set_value(child, rest, new),
+        None => false,
+    }
+}
+
+/// Render `value` on a single line of a block collection, if it is a scalar or is empty
+fn inline(value: &Value, quote: Option<char>) -> Option<String> {
+    match value {
+        Value::Mapping(map) if map.is_empty() => Some("{}".to_owned()),
+        Value::Sequence(items) if items.is_empty() => Some("[]".to_owned()),
+        Value::Mapping(_) | Value::Sequence(_) | Value::Tagged(_) => None,
+        Value::String(s) => Some(match quote {
+            Some('\'') if !s.contains('\n') => format!("'{}'", s.replace('\'', "''")),
+            Some(_) => double_quoted(s),
+            None => {
+                let text = serde_yaml::to_string(s).ok()?;
+                match text.trim_end() {
+                    text if text.contains('\n') => double_quoted(s),
+                    text => text.to_owned(),
+                }
+            }
+        }),
+        other => Some(serde_yaml::to_string(other).ok()?.trim_end().to_owned()),
+    }
+}
+
+/// Render `value` within a flow collection, where indicators such as `,` must be quoted
+fn flow_text(value: &Value, quote: Option<char>) -> String {
+    match value {
+        Value::Mapping(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", flow_text(k, None), flow_text(v, None)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        Value::Sequence(items) => {
+            let items: Vec<_> = items.iter().map(|item| flow_text(item, None)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::String(s) if quote.is_none() && s.contains([',', '[', ']', '{', '}', '#']) => {
+            double_quoted(s)
+        }
+        other => inline(other, quote).unwrap_or_else(|| double_quoted(&key_token(other))),
+    }
+}
+
+fn double_quoted(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Render `value` in block style, with every line indented by `indent` spaces
+fn block(value: &Value, indent: usize, path: &str) -> Result<String, ValidationErrors> {
+    let text = serde_yaml::to_string(value).map_err(|e| invalid(path, &e.to_string()))?;
+    let lines: Vec<_> = text
+        .trim_end()
+        .lines()
+        .map(|line| match line {
+            "" => String::new(),
+            line => format!("{:indent$}{line}", ""),
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"# Front end services
+services:
+  web:
+    image: "nginx:1.25" # pinned for now
+    ports:
+      - "8080:80"
+
+  # The database is only used locally
+  db:
+    image: 'postgres:16'
+    environment: {POSTGRES_DB: app}
+"#;
+
+    #[test]
+    fn test_set() {
+        let mut document = Document::parse(YAML).unwrap();
+        let patch = document.set("/services/web/image", "nginx:1.27").unwrap();
+        assert_eq!(patch.replacement, "\"nginx:1.27\"");
+        assert!(document
+            .as_str()
+            .contains("    image: \"nginx:1.27\" # pinned for now\n"));
+
+        document.set("/services/db/image", "postgres:17").unwrap();
+        assert!(document.as_str().contains("    image: 'postgres:17'\n"));
+
+        document
+            .set("/services/db/environment/POSTGRES_USER", "app")
+            .unwrap();
+        assert!(document
+            .as_str()
+            .contains("environment: {POSTGRES_DB: app, POSTGRES_USER: app}"));
+
+        document
+            .set("/services/web/deploy/resources/limits/cpus", "0.5")
+            .unwrap();
+        assert!(document.as_str().contains(
+            "      - \"8080:80\"\n    deploy:\n      resources:\n        limits:\n          cpus: '0.5'\n\n  # The database"
+        ));
+
+        let compose = document.compose().unwrap();
+        assert_eq!(compose.services["web"].image.as_deref(), Some("nginx:1.27"));
+        assert!(document.as_str().starts_with("# Front end services\n"));
+    }
+
+    #[test]
+    fn test_push() {
+        let mut document = Document::parse(YAML).unwrap();
+        document.push("/services/web/ports", "8443:443").unwrap();
+        assert!(document
+            .as_str()
+            .contains("    ports:\n      - \"8080:80\"\n      - \"8443:443\"\n\n"));
+
+        document.push("/services/db/ports", "5432:5432").unwrap();
+        assert!(document
+            .as_str()
+            .contains("    environment: {POSTGRES_DB: app}\n    ports:\n      - 5432:5432\n"));
+        assert_eq!(document.get("/services/db/ports/0").unwrap(), "5432:5432");
+        document.compose().unwrap();
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut document = Document::parse(YAML).unwrap();
+        document.remove("/services/db").unwrap();
+        assert_eq!(
+            document.as_str(),
+            r#"# Front end services
+services:
+  web:
+    image: "nginx:1.25" # pinned for now
+    ports:
+      - "8080:80"
+
+  # The database is only used locally
+"#
+        );
+
+        document.remove("/services/web/ports/0").unwrap();
+        assert!(document.as_str().contains("    ports: []\n"));
+
+        let mut document =
+            Document::parse("services:\n  web:\n    ports: [\"80:80\", \"443:443\"]\n").unwrap();
+        document.remove("/services/web/ports/0").unwrap();
+        assert!(document.as_str().contains("ports: [\"443:443\"]"));
+    }
+
+    #[test]
+    fn test_invalid_edit() {
+        let yaml = "x-image: &image nginx\nservices:\n  web:\n    image: *image\n";
+        let mut document = Document::parse(yaml).unwrap();
+        let errors = document.set("/services/web/image/tag", "1").unwrap_err();
+        assert_eq!(errors.all_errors()[0].code, ErrorCode::InvalidEdit);
+        assert!(document.remove("/services/db").is_err());
+        assert_eq!(document.as_str(), yaml);
+    }
+}
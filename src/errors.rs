@@ -1,25 +1,212 @@
 //! Library errors
 
-#[derive(Debug)]
-pub enum ValidationError {
-    MissingField(String),
-    InvalidValue(String),
-    InvalidCompose(String),
+use crate::span::{SourceMap, Span};
+
+/// Stable identifier for each kind of validation error
+///
+/// The numeric codes never change meaning between releases, so they are safe to match on or
+/// to persist, unlike the human readable messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The document could not be parsed into a Compose manifest
+    InvalidCompose,
+    /// A required field is missing
+    MissingField,
+    /// A service references a network that is not defined
+    UnknownNetwork,
+    /// A service references a secret that is not defined
+    UnknownSecret,
+    /// A service references a config that is not defined
+    UnknownConfig,
+    /// A service references another service that is not defined
+    UnknownService,
+    /// A container name does not match `[a-zA-Z0-9][a-zA-Z0-9_.-]+`
+    InvalidContainerName,
+    /// A port is not a valid port number
+    InvalidPort,
+    /// `extends` references a service that cannot be used as a base
+    InvalidExtends,
+    /// A network address or subnet cannot be parsed
+    InvalidAddress,
+    /// `dockerfile` and `dockerfile_inline` are both set
+    ConflictingDockerfile,
+    /// A required variable, such as `${VAR:?message}`, has no value
+    MissingVariable,
+    /// A string uses invalid interpolation syntax
+    InvalidInterpolation,
+    /// A referenced file, such as `extends.file` or an `include`, cannot be loaded
+    MissingFile,
+    /// An included file defines a resource that is already defined
+    ConflictingInclude,
+    /// An `include` entry is malformed or includes itself
+    InvalidInclude,
+    /// A `<<` merge key does not reference a mapping or a list of mappings
+    InvalidMergeKey,
+    /// An enabled service depends on a service that is disabled by the selected profiles
+    DisabledDependency,
+    /// A profile name does not match `[a-zA-Z0-9][a-zA-Z0-9_.-]+`
+    InvalidProfile,
+    /// An edit to a [`Document`](crate::document::Document) cannot be applied
+    InvalidEdit,
+    /// The document does not match the compose-spec JSON Schema
+    SchemaViolation,
+    /// A key is not part of the Compose specification and is ignored
+    UnknownKey,
+    /// A field is deprecated or obsolete in the Compose specification
+    Deprecated,
+    /// Services depend on each other in a cycle, or a service depends on itself
+    DependencyCycle,
+    /// Services publish the same host port on the same address and protocol
+    PortConflict,
+    /// A problem reported by a [`Rule`](crate::rules::Rule), identified by the rule id
+    Rule(&'static str),
 }
 
-impl std::fmt::Display for ValidationError {
+impl ErrorCode {
+    /// The stable code, e.g. `CV0008`, or the rule id for [`ErrorCode::Rule`]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidCompose => "CV0001",
+            ErrorCode::MissingField => "CV0002",
+            ErrorCode::UnknownNetwork => "CV0003",
+            ErrorCode::UnknownSecret => "CV0004",
+            ErrorCode::UnknownConfig => "CV0005",
+            ErrorCode::UnknownService => "CV0006",
+            ErrorCode::InvalidContainerName => "CV0007",
+            ErrorCode::InvalidPort => "CV0008",
+            ErrorCode::InvalidExtends => "CV0009",
+            ErrorCode::InvalidAddress => "CV0010",
+            ErrorCode::ConflictingDockerfile => "CV0011",
+            ErrorCode::MissingVariable => "CV0012",
+            ErrorCode::InvalidInterpolation => "CV0013",
+            ErrorCode::MissingFile => "CV0014",
+            ErrorCode::ConflictingInclude => "CV0015",
+            ErrorCode::InvalidInclude => "CV0016",
+            ErrorCode::InvalidMergeKey => "CV0017",
+            ErrorCode::DisabledDependency => "CV0018",
+            ErrorCode::InvalidProfile => "CV0019",
+            ErrorCode::InvalidEdit => "CV0020",
+            ErrorCode::SchemaViolation => "CV0021",
+            ErrorCode::UnknownKey => "CV0022",
+            ErrorCode::Deprecated => "CV0023",
+            ErrorCode::DependencyCycle => "CV0024",
+            ErrorCode::PortConflict => "CV0025",
+            ErrorCode::Rule(id) => id,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// How serious a [`ValidationError`] is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Informational, such as a hint about a better way to write something
+    Info,
+    /// Reported, but does not prevent the manifest from being built
+    Warning,
+    /// Prevents the manifest from being built
+    #[default]
+    Error,
+}
+
+impl std::fmt::Display for Severity {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            ValidationError::MissingField(field) => write!(f, "Missing field: {}", field),
-            ValidationError::InvalidValue(value) => write!(f, "Invalid value: {}", value),
-            ValidationError::InvalidCompose(value) => write!(f, "Invalid compose file: {}", value),
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single validation error
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    pub code: ErrorCode,
+
+    pub severity: Severity,
+
+    /// JSON pointer style path of the offending node, e.g. `/services/gitlab/expose/2`.
+    /// Empty when the error concerns the whole document.
+    pub path: String,
+
+    /// The offending value, if there is one
+    pub value: Option<String>,
+
+    pub message: String,
+
+    /// Span of the offending node, if the error could be traced back to the source
+    pub span: Option<Span>,
+}
+
+impl ValidationError {
+    pub(crate) fn new(code: ErrorCode, path: &str, message: impl Into<String>) -> Self {
+        ValidationError {
+            code,
+            severity: Severity::Error,
+            path: path.to_owned(),
+            value: None,
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    pub(crate) fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    pub(crate) fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub(crate) fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Report an error found at its path within `file` at `path` of the root manifest instead
+    pub(crate) fn nested(self, file: &str, path: &str) -> Self {
+        ValidationError {
+            message: format!("{file}{}: {}", self.path, self.message),
+            path: path.to_owned(),
+            ..self
+        }
+    }
+
+    /// Whether the error concerns the node at `prefix` or one of its descendants
+    pub fn is_under(&self, prefix: &str) -> bool {
+        self.path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'))
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+        if self.severity != Severity::Error {
+            write!(f, "{}: ", self.severity)?;
         }
+        write!(f, "[{}] ", self.code)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
     }
 }
 
 impl std::error::Error for ValidationError {}
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ValidationErrors {
     errors: Vec<ValidationError>,
 }
@@ -33,13 +220,67 @@ impl ValidationErrors {
         self.errors.push(error);
     }
 
+    /// Whether any of the errors has [`Severity::Error`], as opposed to only warnings
     pub fn has_errors(&self) -> bool {
-        !self.errors.is_empty()
+        self.errors.iter().any(|e| e.severity == Severity::Error)
+    }
+
+    /// All errors with the given severity
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &ValidationError> {
+        self.errors.iter().filter(move |e| e.severity == severity)
     }
 
     pub fn all_errors(&self) -> &[ValidationError] {
         &self.errors
     }
+
+    /// All errors with the given code
+    pub fn with_code(&self, code: ErrorCode) -> impl Iterator<Item = &ValidationError> {
+        self.errors.iter().filter(move |e| e.code == code)
+    }
+
+    /// All errors for the node at `prefix` or any of its descendants,
+    /// e.g. `/services/gitlab` for every error within the `gitlab` service
+    pub fn under<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a ValidationError> {
+        self.errors.iter().filter(move |e| e.is_under(prefix))
+    }
+
+    /// Attach source spans to every error that does not have one yet
+    pub(crate) fn locate(&mut self, source: &SourceMap) {
+        for error in self.errors.iter_mut().filter(|e| e.span.is_none()) {
+            error.span = source.get(&error.path);
+        }
+    }
+
+    /// Replace the span of every error with the one found in `source`
+    pub(crate) fn relocate(&mut self, source: &SourceMap) {
+        for error in self.errors.iter_mut() {
+            error.span = source.get(&error.path);
+        }
+    }
+}
+
+impl Extend<ValidationError> for ValidationErrors {
+    fn extend<T: IntoIterator<Item = ValidationError>>(&mut self, iter: T) {
+        self.errors.extend(iter);
+    }
+}
+
+impl IntoIterator for ValidationErrors {
+    type Item = ValidationError;
+    type IntoIter = std::vec::IntoIter<ValidationError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl From<ValidationError> for ValidationErrors {
+    fn from(error: ValidationError) -> Self {
+        ValidationErrors {
+            errors: vec![error],
+        }
+    }
 }
 
 impl std::fmt::Display for ValidationErrors {
@@ -52,3 +293,54 @@ impl std::fmt::Display for ValidationErrors {
 }
 
 impl std::error::Error for ValidationErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_errors() {
+        let mut errors = ValidationErrors::new();
+        errors.add_error(ValidationError::new(
+            ErrorCode::InvalidPort,
+            "/services/gitlab/expose/2",
+            "Invalid port",
+        ));
+        errors.add_error(ValidationError::new(
+            ErrorCode::UnknownService,
+            "/services/gitlab-runner/depends_on/0",
+            "Invalid service for depends_on",
+        ));
+
+        assert_eq!(errors.with_code(ErrorCode::InvalidPort).count(), 1);
+        assert_eq!(errors.under("/services/gitlab").count(), 1);
+        assert_eq!(errors.under("/services").count(), 2);
+        assert_eq!(errors.under("").count(), 2);
+        assert_eq!(errors.under("/networks").count(), 0);
+    }
+
+    #[test]
+    fn test_severity() {
+        let mut errors = ValidationErrors::new();
+        errors.add_error(
+            ValidationError::new(ErrorCode::Deprecated, "/version", "version is obsolete")
+                .with_severity(Severity::Warning),
+        );
+        assert!(!errors.has_errors());
+        assert_eq!(
+            errors.all_errors()[0].to_string(),
+            "warning: [CV0023] /version: version is obsolete"
+        );
+
+        errors.add_error(ValidationError::new(
+            ErrorCode::InvalidPort,
+            "/services/gitlab/expose/2",
+            "Invalid port",
+        ));
+        assert!(errors.has_errors());
+        assert_eq!(errors.with_severity(Severity::Warning).count(), 1);
+        assert_eq!(errors.with_severity(Severity::Error).count(), 1);
+        assert_eq!(errors.with_severity(Severity::Info).count(), 0);
+        assert!(Severity::Info < Severity::Warning && Severity::Warning < Severity::Error);
+    }
+}
@@ -0,0 +1,475 @@
+//! Resolution of `extends`
+//!
+//! Inlines the service that a service [extends](https://docs.docker.com/compose/compose-file/05-services/#extends),
+//! from the same manifest or from another file loaded through a [`FileLoader`]. Chains are
+//! followed to their end, and each service is merged on top of its base with the same rules
+//! that apply when merging documents.
+
+use std::collections::HashMap;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    loader::{self, FileLoader},
+    merge, merge_keys,
+    span::pointer,
+};
+
+/// A service within a file, `None` being the root manifest
+type ServiceKey = (Option<String>, String);
+
+struct Resolver<'a> {
+    loader: &'a dyn FileLoader,
+    /// The services of each file, parsed on first use
+    files: HashMap<Option<String>, Mapping>,
+}
+
+/// Resolve the `extends` chains of the `names` services of `services`
+///
+/// Returns the flattened services, which no longer have an `extends` field.
+pub(crate) fn resolve(
+    services: Mapping,
+    names: &[&str],
+    loader: &dyn FileLoader,
+) -> Result<Mapping, ValidationErrors> {
+    let mut resolver = Resolver {
+        loader,
+        files: HashMap::from([(None, services)]),
+    };
+
+    let mut errors = ValidationErrors::new();
+    let mut resolved = Mapping::new();
+    for name in names {
+        let path = pointer(&pointer("/services", name), "extends");
+        match resolver.resolve(None, name, &path, &mut Vec::new()) {
+            Ok(mut service) => {
+                merge::strip_tags(&mut service);
+                resolved.insert((*name).into(), service);
+            }
+            Err(e) => errors.extend(e),
+        }
+    }
+
+    if errors.has_errors() {
+        return Err(errors);
+    }
+    Ok(resolved)
+}
+
+impl Resolver<'_> {
+    fn resolve(
+        &mut self,
+        file: Option<String>,
+        name: &str,
+        path: &str,
+        chain: &mut Vec<ServiceKey>,
+    ) -> Result<Value, ValidationErrors> {
+        let key = (file.clone(), name.to_owned());
+        if chain.contains(&key) {
+            chain.push(key);
+            let cycle: Vec<String> = chain.iter().map(describe).collect();
+            return Err(ValidationError::new(
+                ErrorCode::InvalidExtends,
+                path,
+                format!("Circular extends: {}", cycle.join(" -> ")),
+            )
+            .into());
+        }
+
+        let Some(mut service) = self.services(&file, path)?.get(name).cloned() else {
+            return Err(ValidationError::new(
+                ErrorCode::UnknownService,
+                path,
+                format!("Extends references invalid service {}", describe(&key)),
+            )
+            .with_value(name)
+            .into());
+        };
+
+        // Relative paths of a service from another file are relative to that file
+        if let Some(file) = &file {
+            loader::rebase_service(&mut service, &loader::directory(file));
+        }
+
+        let Some(extends) = service.as_mapping_mut().and_then(|s| s.remove("extends")) else {
+            return Ok(service);
+        };
+        let (base_file, base_name) = match &extends {
+            // The short syntax names a service in the same file
+            Value::String(name) => (None, name.as_str()),
+            Value::Mapping(extends) => match extends.get("service").and_then(Value::as_str) {
+                Some(name) => (extends.get("file").and_then(Value::as_str), name),
+                None => {
+                    return Err(ValidationError::new(
+                        ErrorCode::MissingField,
+                        path,
+                        format!("Extends of {} has no service", describe(&key)),
+                    )
+                    .into())
+                }
+            },
+            _ => {
+                return Err(ValidationError::new(
+                    ErrorCode::InvalidExtends,
+                    path,
+                    format!(
+                        "Extends of {} must be a string or a mapping",
+                        describe(&key)
+                    ),
+                )
+                .into())
+            }
+        };
+        let base_file = match base_file {
+            Some(base_file) => Some(loader::join(file.as_deref(), base_file)),
+            None => file,
+        };
+
+        let base_key = (base_file.clone(), base_name.to_owned());
+        chain.push(key);
+        let mut base = self.resolve(base_file, base_name, path, chain)?;
+        chain.pop();
+
+        let restrictions = restrictions(&base);
+        if !restrictions.is_empty() {
+            let mut errors = ValidationErrors::new();
+            for message in restrictions {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::InvalidExtends,
+                        path,
+                        format!("{message}: {}", describe(&base_key)),
+                    )
+                    .with_value(base_name),
+                );
+            }
+            return Err(errors);
+        }
+
+        merge::merge_service(&mut base, service);
+        Ok(base)
+    }
+
+    /// The services defined in `file`, loading and parsing it if needed
+    fn services(
+        &mut self,
+        file: &Option<String>,
+        path: &str,
+    ) -> Result<&Mapping, ValidationErrors> {
+        if !self.files.contains_key(file) {
+            let Some(name) = file else {
+                unreachable!("the root manifest is always present");
+            };
+            let contents = self.loader.load(name).map_err(|e| {
+                ValidationErrors::from(
+                    ValidationError::new(
+                        ErrorCode::MissingFile,
+                        path,
+                        format!("Cannot load {name}: {e}"),
+                    )
+                    .with_value(name),
+                )
+            })?;
+            let mut document: Value = serde_yaml::from_str(&contents).map_err(|e| {
+                ValidationErrors::from(
+                    ValidationError::new(ErrorCode::InvalidCompose, path, format!("{name}: {e}"))
+                        .with_value(name),
+                )
+            })?;
+            let mut problems = ValidationErrors::new();
+            merge_keys::apply(&mut document, "", &mut problems);
+            if problems.has_errors() {
+                let mut errors = ValidationErrors::new();
+                errors.extend(problems.into_iter().map(|e| e.nested(name, path)));
+                return Err(errors);
+            }
+            let services = match document.get("services") {
+                Some(Value::Mapping(services)) => services.clone(),
+                _ => Mapping::new(),
+            };
+            self.files.insert(file.clone(), services);
+        }
+        Ok(&self.files[file])
+    }
+}
+
+/// Why `service` cannot be used as the base of `extends`
+///
+/// Services that depend on other services cannot be extended, so any key that introduces such
+/// a dependency is reported: `depends_on`, `links`, `volumes_from`, and `ipc`, `pid` or
+/// `network_mode` in service or container mode.
+pub(crate) fn restrictions(service: &Value) -> Vec<&'static str> {
+    let present = |key: &str| match service.get(key) {
+        None | Some(Value::Null) => false,
+        Some(Value::Sequence(items)) => !items.is_empty(),
+        Some(Value::Mapping(items)) => !items.is_empty(),
+        Some(_) => true,
+    };
+    let shared = |key: &str| {
+        service
+            .get(key)
+            .and_then(Value::as_str)
+            .is_some_and(|mode| mode.starts_with("service:") || mode.starts_with("container:"))
+    };
+
+    let mut restrictions = Vec::new();
+    if present("depends_on") {
+        restrictions.push("Extends cannot extend another service that has a depends_on");
+    }
+    if present("links") {
+        restrictions.push("Extends cannot have any links");
+    }
+    if present("volumes_from") {
+        restrictions.push("Extends cannot have any volumes_from");
+    }
+    if shared("ipc") {
+        restrictions.push("Extends cannot have an IPC mode that references another service");
+    }
+    if shared("pid") {
+        restrictions.push("Extends cannot have a PID mode that references another service");
+    }
+    if shared("network_mode") {
+        restrictions.push("Extends cannot extend a service that has a network dependency");
+    }
+    restrictions
+}
+
+fn describe((file, name): &ServiceKey) -> String {
+    match file {
+        Some(file) => format!("{file}:{name}"),
+        None => name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Compose;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_resolve_chain() {
+        let yaml = r#"
+        services:
+          web:
+            extends:
+              service: app
+            environment:
+              - MODE=web
+            command: ["serve"]
+            x-team: web
+          app:
+            extends:
+              file: common/base.yml
+              service: base
+            environment:
+              - DEBUG=false
+        "#;
+        let base = r#"
+        services:
+          base:
+            extends:
+              service: runtime
+            image: app:1.0
+            command: ["run"]
+            environment:
+              - MODE=worker
+            x-owner: platform
+          runtime:
+            read_only: true
+        "#;
+        let files = HashMap::from([("common/base.yml".to_owned(), base.to_owned())]);
+
+        let compose = Compose::new(yaml).unwrap();
+        let web = compose.resolve_service("web", &files).unwrap();
+        assert!(web.extends.is_none());
+        assert_eq!(web.image.as_deref(), Some("app:1.0"));
+        assert_eq!(web.read_only, Some(true));
+        let Some(crate::services::Command::List(command)) = web.command else {
+            panic!("command should be a list");
+        };
+        assert_eq!(command, ["serve"]);
+        let Some(crate::services::Labels::List(environment)) = web.environment else {
+            panic!("environment should be a list");
+        };
+        assert!(environment.contains(&"MODE=web".to_owned()));
+        assert!(environment.contains(&"DEBUG=false".to_owned()));
+        // Extensions of the service and of its bases are kept
+        let extensions: Vec<_> = web.extensions.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(extensions, ["x-owner", "x-team"]);
+
+        let resolved = compose.resolve_extends(&files).unwrap();
+        assert!(resolved.services.values().all(|s| s.extends.is_none()));
+        assert_eq!(
+            resolved.services["web"].extensions,
+            compose.resolve_service("web", &files).unwrap().extensions
+        );
+    }
+
+    #[test]
+    fn test_short_syntax() {
+        let yaml = r#"
+        services:
+          web:
+            extends: base
+            command: ["serve"]
+          base:
+            image: app:1.0
+          worker:
+            extends: missing
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.path, "/services/worker/extends");
+        assert_eq!(error.value.as_deref(), Some("missing"));
+
+        let compose = Compose::new(&yaml.replace("extends: missing", "image: worker")).unwrap();
+        let web = compose.resolve_service("web", &HashMap::new()).unwrap();
+        assert!(web.extends.is_none());
+        assert_eq!(web.image.as_deref(), Some("app:1.0"));
+    }
+
+    #[test]
+    fn test_resolve_cycle() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            extends:
+              file: other.yml
+              service: app
+        "#;
+        let other = r#"
+        services:
+          app:
+            extends:
+              file: ./other.yml
+              service: worker
+          worker:
+            extends:
+              service: app
+        "#;
+        let files = HashMap::from([("other.yml".to_owned(), other.to_owned())]);
+
+        let compose = Compose::new(yaml).unwrap();
+        let errors = compose.resolve_extends(&files).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.path, "/services/web/extends");
+        assert_eq!(
+            error.message,
+            "Circular extends: web -> other.yml:app -> other.yml:worker -> other.yml:app"
+        );
+
+        let errors = compose.resolve_extends(&HashMap::new()).unwrap_err();
+        assert_eq!(
+            errors.all_errors()[0].code,
+            crate::errors::ErrorCode::MissingFile
+        );
+    }
+
+    #[test]
+    fn test_rebase_paths() {
+        let yaml = r#"
+        services:
+          web:
+            extends:
+              file: common/base.yml
+              service: base
+        "#;
+        let base = r#"
+        services:
+          base:
+            image: app
+            build:
+              context: ./app
+            env_file:
+              - base.env
+              - ../shared.env
+            volumes:
+              - ./static:/srv/static:ro
+              - data:/data
+              - /etc/hosts:/etc/hosts
+              - type: bind
+                source: ./config
+                target: /config
+        "#;
+        let files = HashMap::from([("common/base.yml".to_owned(), base.to_owned())]);
+
+        let compose = Compose::new(yaml).unwrap();
+        let web = compose.resolve_service("web", &files).unwrap();
+        let web = serde_yaml::to_value(&web).unwrap();
+        assert_eq!(web["build"]["context"], "./common/app");
+        assert_eq!(web["env_file"][0], "./common/base.env");
+        assert_eq!(web["env_file"][1], "./shared.env");
+        assert_eq!(web["volumes"][0], "./common/static:/srv/static:ro");
+        assert_eq!(web["volumes"][1], "data:/data");
+        assert_eq!(web["volumes"][2], "/etc/hosts:/etc/hosts");
+        assert_eq!(web["volumes"][3]["source"], "./common/config");
+
+        let mut service: serde_yaml::Value =
+            serde_yaml::from_str("{build: ., env_file: [{path: ./.env}]}").unwrap();
+        crate::loader::rebase_service(&mut service, "common");
+        assert_eq!(service["build"], "./common");
+        assert_eq!(service["env_file"][0]["path"], "./common/.env");
+    }
+
+    #[test]
+    fn test_restrictions() {
+        let yaml = r#"
+        services:
+          web:
+            extends:
+              service: base
+          base:
+            image: app
+            ipc: host
+            pid: "service:db"
+            depends_on: [db]
+          db:
+            image: db
+        "#;
+
+        // Both the validator and the resolver refuse the same base
+        let errors = Compose::new(yaml).unwrap_err();
+        let messages: Vec<_> = errors
+            .all_errors()
+            .iter()
+            .map(|e| e.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Extends cannot extend another service that has a depends_on",
+                "Extends cannot have a PID mode that references another service",
+            ]
+        );
+
+        let yaml = r#"
+        services:
+          web:
+            extends:
+              file: base.yml
+              service: base
+        "#;
+        let base = r#"
+        services:
+          base:
+            image: app
+            ipc: host
+            network_mode: "service:db"
+        "#;
+        let files = HashMap::from([("base.yml".to_owned(), base.to_owned())]);
+        let errors = Compose::new(yaml)
+            .unwrap()
+            .resolve_extends(&files)
+            .unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(errors.all_errors().len(), 1);
+        assert_eq!(error.path, "/services/web/extends");
+        assert_eq!(
+            error.message,
+            "Extends cannot extend a service that has a network dependency: base.yml:base"
+        );
+    }
+}
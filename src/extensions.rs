@@ -0,0 +1,265 @@
+//! Extension fields
+//!
+//! Keys starting with `x-` are [extensions](https://docs.docker.com/compose/compose-file/11-extension/)
+//! that Compose itself ignores. They are kept on the element they appear in, so that they survive
+//! a round trip through [`Compose`] and can be read back into user defined types.
+
+use indexmap::IndexMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+
+use crate::{
+    compose::Compose,
+    services::{
+        build::Build, networks::Networks, ports::Ports, secrets::Secret, volumes::Volumes, Config,
+        Service,
+    },
+};
+
+/// The `x-` fields of an element, keyed by their full name such as `x-team`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Extensions(IndexMap<String, Value>);
+
+impl Extensions {
+    /// The raw value of the extension `key`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    /// Deserialize the extension `key` into `T`, returning `None` if it is not set
+    pub fn deserialize<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_yaml::Error> {
+        self.0
+            .get(key)
+            .map(|value| serde_yaml::from_value(value.clone()))
+            .transpose()
+    }
+
+    /// Set the extension `key`, which should start with `x-`
+    pub fn insert(&mut self, key: impl Into<String>, value: Value) -> Option<Value> {
+        self.0.insert(key.into(), value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Collect the `x-` keys of `value`, if it is a mapping
+    fn from_mapping(value: &Value) -> Self {
+        let Some(mapping) = value.as_mapping() else {
+            return Extensions::default();
+        };
+        Extensions(
+            mapping
+                .iter()
+                .filter_map(|(k, v)| Some((k.as_str()?, v)))
+                .filter(|(k, _)| k.starts_with("x-"))
+                .map(|(k, v)| (k.to_owned(), v.clone()))
+                .collect(),
+        )
+    }
+}
+
+/// Fill in the extensions of `compose` and its elements from the document it was built from
+///
+/// Extensions are not deserialized along with the rest of the manifest, because the derived
+/// implementations have no way of collecting keys by prefix.
+pub(crate) fn capture(compose: &mut Compose, document: &Value) {
+    compose.extensions = Extensions::from_mapping(document);
+
+    for (name, service) in compose.services.iter_mut() {
+        capture_service(service, &document["services"][name.as_str()]);
+    }
+    for (name, network) in compose.networks.iter_mut().flatten() {
+        if let Some(network) = network {
+            network.extensions = Extensions::from_mapping(&document["networks"][name.as_str()]);
+        }
+    }
+    for (name, volume) in compose.volumes.iter_mut().flatten() {
+        if let Some(volume) = volume {
+            volume.extensions = Extensions::from_mapping(&document["volumes"][name.as_str()]);
+        }
+    }
+    for (name, config) in compose.configs.iter_mut().flatten() {
+        if let Some(config) = config {
+            config.extensions = Extensions::from_mapping(&document["configs"][name.as_str()]);
+        }
+    }
+    for (name, secret) in compose.secrets.iter_mut().flatten() {
+        if let Some(secret) = secret {
+            secret.extensions = Extensions::from_mapping(&document["secrets"][name.as_str()]);
+        }
+    }
+}
+
+/// Fill in the extensions of `service` and of its nested elements from `document`
+pub(crate) fn capture_service(service: &mut Service, document: &Value) {
+    service.extensions = Extensions::from_mapping(document);
+
+    if let Some(Build::Map(build)) = &mut service.build {
+        build.extensions = Extensions::from_mapping(&document["build"]);
+    }
+    if let Some(deploy) = &mut service.deploy {
+        deploy.extensions = Extensions::from_mapping(&document["deploy"]);
+    }
+    if let Some(healthcheck) = &mut service.healthcheck {
+        healthcheck.extensions = Extensions::from_mapping(&document["healthcheck"]);
+    }
+    if let Some(logging) = &mut service.logging {
+        logging.extensions = Extensions::from_mapping(&document["logging"]);
+    }
+    if let Some(Networks::Map(networks)) = &mut service.networks {
+        for (name, network) in networks.iter_mut() {
+            network.extensions = Extensions::from_mapping(&document["networks"][name.as_str()]);
+        }
+    }
+    for (i, config) in service.configs.iter_mut().flatten().enumerate() {
+        if let Config::Long(config) = config {
+            config.extensions = Extensions::from_mapping(&document["configs"][i]);
+        }
+    }
+    for (i, secret) in service.secrets.iter_mut().flatten().enumerate() {
+        if let Secret::Long(secret) = secret {
+            secret.extensions = Extensions::from_mapping(&document["secrets"][i]);
+        }
+    }
+    for (i, volume) in service.volumes.iter_mut().flatten().enumerate() {
+        if let Volumes::Long(volume) = volume {
+            volume.extensions = Extensions::from_mapping(&document["volumes"][i]);
+        }
+    }
+    for (i, port) in service.ports.iter_mut().flatten().enumerate() {
+        if let Ports::Long(port) = port {
+            port.extensions = Extensions::from_mapping(&document["ports"][i]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Compose;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Team {
+        name: String,
+        slack: String,
+    }
+
+    #[test]
+    fn test_extensions() {
+        let yaml = r#"
+        x-deploy-meta:
+          region: eu-west-1
+        services:
+          web:
+            image: web
+            x-team:
+              name: platform
+              slack: platform-team
+        networks:
+          backend:
+            x-owner: platform
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        assert_eq!(
+            compose.extensions.get("x-deploy-meta").unwrap()["region"],
+            "eu-west-1"
+        );
+
+        let web = &compose.services["web"];
+        let team: Team = web.extension("x-team").unwrap().unwrap();
+        assert_eq!(team.name, "platform");
+        assert!(web.extension::<Team>("x-missing").unwrap().is_none());
+        assert!(web.extension::<u32>("x-team").is_err());
+
+        // Extensions survive a round trip
+        let compose = Compose::new(&compose.to_string()).unwrap();
+        assert_eq!(
+            compose.services["web"].extension::<Team>("x-team").unwrap(),
+            Some(team)
+        );
+        let backend = compose.networks.unwrap()["backend"].clone().unwrap();
+        assert_eq!(backend.extensions.get("x-owner").unwrap(), "platform");
+    }
+
+    #[test]
+    fn test_nested_extensions() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            build:
+              context: .
+              x-meta: build
+            deploy:
+              x-meta: deploy
+            healthcheck:
+              interval: 30s
+              x-meta: healthcheck
+            logging:
+              driver: json-file
+              x-meta: logging
+            networks:
+              backend:
+                x-meta: network
+            ports:
+              - target: 80
+                x-meta: port
+            volumes:
+              - type: volume
+                source: data
+                target: /data
+                x-meta: volume
+            secrets:
+              - source: token
+                x-meta: secret
+            configs:
+              - source: app
+                x-meta: config
+        networks:
+          backend:
+        volumes:
+          data:
+        secrets:
+          token:
+            file: ./token
+        configs:
+          app:
+            file: ./app.conf
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let again = Compose::new(&compose.to_string()).unwrap();
+        let web: serde_yaml::Value = serde_yaml::to_value(&again.services["web"]).unwrap();
+        for path in [
+            &web["build"],
+            &web["deploy"],
+            &web["healthcheck"],
+            &web["logging"],
+            &web["networks"]["backend"],
+            &web["ports"][0],
+            &web["volumes"][0],
+            &web["secrets"][0],
+            &web["configs"][0],
+        ] {
+            assert!(path["x-meta"].is_string(), "{path:?}");
+        }
+        assert_eq!(web["deploy"]["x-meta"], "deploy");
+
+        let deploy = again.services["web"].deploy.as_ref().unwrap();
+        assert_eq!(
+            deploy.extensions.deserialize::<String>("x-meta").unwrap(),
+            Some("deploy".to_owned())
+        );
+    }
+}
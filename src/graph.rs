@@ -0,0 +1,719 @@
+//! Relationships between services
+//!
+//! A service can rely on another one in several ways besides `depends_on`, such as sharing its
+//! network stack with `network_mode: service:db`. The [`DependencyGraph`] gathers all of them,
+//! tagged by [`EdgeKind`], and finds the cycles Compose would refuse to start.
+
+use std::fmt::Display;
+
+use crate::{
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    services::{
+        build::{AdditionalContexts, Build},
+        DependsOn, DependsOnCondition, Service,
+    },
+    span::pointer,
+    Compose,
+};
+
+/// How a service relies on another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// `depends_on`
+    DependsOn,
+    /// `links`
+    Link,
+    /// `volumes_from`, except for `container:` sources
+    VolumesFrom,
+    /// `network_mode: service:NAME`
+    NetworkMode,
+    /// `ipc: service:NAME`
+    Ipc,
+    /// A `service:NAME` entry of `build.additional_contexts`
+    BuildContext,
+}
+
+impl Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let field = match self {
+            EdgeKind::DependsOn => "depends_on",
+            EdgeKind::Link => "links",
+            EdgeKind::VolumesFrom => "volumes_from",
+            EdgeKind::NetworkMode => "network_mode",
+            EdgeKind::Ipc => "ipc",
+            EdgeKind::BuildContext => "build.additional_contexts",
+        };
+        write!(f, "{field}")
+    }
+}
+
+/// A service relying on another one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    /// The service that relies on `to`
+    pub from: String,
+
+    pub to: String,
+
+    pub kind: EdgeKind,
+
+    /// JSON pointer style path of the reference, e.g. `/services/web/depends_on/db`
+    pub path: String,
+
+    /// The condition `to` must meet before `from` starts, for [`EdgeKind::DependsOn`] only
+    pub condition: Option<DependsOnCondition>,
+
+    /// Whether `from` fails to start without `to`. Only `depends_on` entries can be optional.
+    pub required: bool,
+}
+
+/// The relationships between the services of a manifest
+///
+/// Only references to services that are defined in the manifest become edges.
+///
+/// ```
+/// use compose_validatr::{graph::EdgeKind, Compose};
+///
+/// let yaml = r#"
+/// services:
+///   web:
+///     image: web
+///     depends_on: [db]
+///     network_mode: service:proxy
+///   db:
+///     image: postgres
+///   proxy:
+///     image: nginx
+/// "#;
+///
+/// let graph = Compose::new(yaml).unwrap().dependency_graph();
+/// let kinds: Vec<_> = graph.dependencies("web").map(|e| (e.to.as_str(), e.kind)).collect();
+/// assert_eq!(kinds, [("db", EdgeKind::DependsOn), ("proxy", EdgeKind::NetworkMode)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DependencyGraph {
+    services: Vec<String>,
+    edges: Vec<Edge>,
+}
+
+impl DependencyGraph {
+    /// The graph of the services of `compose`, see also [`Compose::dependency_graph`]
+    pub fn new(compose: &Compose) -> Self {
+        let services: Vec<String> = compose.services.keys().cloned().collect();
+        let edges = compose
+            .services
+            .iter()
+            .flat_map(|(name, service)| edges(name, service))
+            .filter(|edge| compose.services.contains_key(&edge.to))
+            .collect();
+        DependencyGraph { services, edges }
+    }
+
+    /// Every service, in the order of the manifest
+    pub fn services(&self) -> impl Iterator<Item = &str> {
+        self.services.iter().map(String::as_str)
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// The edges from `service` to the services it relies on
+    pub fn dependencies<'a>(&'a self, service: &'a str) -> impl Iterator<Item = &'a Edge> {
+        self.edges.iter().filter(move |e| e.from == service)
+    }
+
+    /// The edges to `service` from the services that rely on it
+    pub fn dependents<'a>(&'a self, service: &'a str) -> impl Iterator<Item = &'a Edge> {
+        self.edges.iter().filter(move |e| e.to == service)
+    }
+
+    /// The cycles of the graph, each as the services along it with the first one repeated at
+    /// the end, e.g. `["a", "b", "a"]`, or `["a", "a"]` for a service that relies on itself
+    ///
+    /// Every elementary cycle is reported once, starting from its first service in the order of
+    /// the manifest, including cycles that share services with others. It is closed by the
+    /// edge back to that service, see [`DependencyGraph::validate`].
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        self.closing_edges()
+            .into_iter()
+            .map(|(cycle, _)| cycle)
+            .collect()
+    }
+
+    /// Report every cycle as an [`ErrorCode::DependencyCycle`] error, at the path of the
+    /// reference that closes it
+    pub fn validate(&self) -> ValidationErrors {
+        let mut errors = ValidationErrors::new();
+        for (cycle, edge) in self.closing_edges() {
+            let message = if cycle.len() == 2 {
+                format!(
+                    "Service {} depends on itself through {}",
+                    edge.from, edge.kind
+                )
+            } else {
+                format!("Dependency cycle {}", cycle.join(" -> "))
+            };
+            errors.add_error(
+                ValidationError::new(ErrorCode::DependencyCycle, &edge.path, message)
+                    .with_value(cycle.join(" -> ")),
+            );
+        }
+        errors
+    }
+
+    /// Each cycle along with the edge that closes it
+    ///
+    /// Every elementary cycle is found once, starting from its first service in the order of the
+    /// manifest: the search from each service only follows services after it, within its
+    /// strongly connected component.
+    fn closing_edges(&self) -> Vec<(Vec<String>, &Edge)> {
+        struct Search<'a> {
+            graph: &'a DependencyGraph,
+            components: Vec<usize>,
+            start: usize,
+            stack: Vec<usize>,
+            found: Vec<(Vec<String>, &'a Edge)>,
+        }
+
+        impl<'a> Search<'a> {
+            fn visit(&mut self, node: usize) {
+                self.stack.push(node);
+                let graph = self.graph;
+                let mut followed: Vec<usize> = Vec::new();
+                for edge in graph.dependencies(&graph.services[node]) {
+                    let Some(next) = graph.index(&edge.to) else {
+                        continue;
+                    };
+                    // Services can rely on each other through several fields at once
+                    if followed.contains(&next) {
+                        continue;
+                    }
+                    followed.push(next);
+                    if next == self.start {
+                        let mut cycle: Vec<String> = self
+                            .stack
+                            .iter()
+                            .map(|&n| graph.services[n].clone())
+                            .collect();
+                        cycle.push(edge.to.clone());
+                        self.found.push((cycle, edge));
+                    } else if next > self.start
+                        && self.components[next] == self.components[self.start]
+                        && !self.stack.contains(&next)
+                    {
+                        self.visit(next);
+                    }
+                }
+                self.stack.pop();
+            }
+        }
+
+        let mut search = Search {
+            graph: self,
+            components: self.components(),
+            start: 0,
+            stack: Vec::new(),
+            found: Vec::new(),
+        };
+        for node in 0..self.services.len() {
+            search.start = node;
+            search.visit(node);
+        }
+        search.found
+    }
+
+    /// The strongly connected component of every service, found with Tarjan's algorithm
+    fn components(&self) -> Vec<usize> {
+        struct Tarjan<'a> {
+            graph: &'a DependencyGraph,
+            indices: Vec<Option<usize>>,
+            lowlinks: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            components: Vec<usize>,
+            count: usize,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, node: usize) {
+                let index = self.count;
+                self.count += 1;
+                self.indices[node] = Some(index);
+                self.lowlinks[node] = index;
+                self.stack.push(node);
+                self.on_stack[node] = true;
+
+                let graph = self.graph;
+                for edge in graph.dependencies(&graph.services[node]) {
+                    let Some(next) = graph.index(&edge.to) else {
+                        continue;
+                    };
+                    match self.indices[next] {
+                        None => {
+                            self.visit(next);
+                            self.lowlinks[node] = self.lowlinks[node].min(self.lowlinks[next]);
+                        }
+                        Some(next_index) if self.on_stack[next] => {
+                            self.lowlinks[node] = self.lowlinks[node].min(next_index);
+                        }
+                        Some(_) => (),
+                    }
+                }
+
+                // The root of a component, which is every service left on the stack above it
+                if self.lowlinks[node] == index {
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack[member] = false;
+                        self.components[member] = node;
+                        if member == node {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        let len = self.services.len();
+        let mut tarjan = Tarjan {
+            graph: self,
+            indices: vec![None; len],
+            lowlinks: vec![0; len],
+            stack: Vec::new(),
+            on_stack: vec![false; len],
+            components: vec![0; len],
+            count: 0,
+        };
+        for node in 0..len {
+            if tarjan.indices[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        tarjan.components
+    }
+
+    fn index(&self, service: &str) -> Option<usize> {
+        self.services.iter().position(|s| s == service)
+    }
+
+    /// The order in which the services start and stop, see [`Compose::startup_plan`]
+    pub fn startup_plan(&self) -> Result<StartupPlan, ValidationErrors> {
+        let errors = self.validate();
+        if errors.has_errors() {
+            return Err(errors);
+        }
+
+        let mut waits_for: Vec<(String, Vec<(String, DependsOnCondition)>)> = Vec::new();
+        for service in &self.services {
+            let mut waits: Vec<(String, DependsOnCondition)> = Vec::new();
+            for edge in self.dependencies(service) {
+                // The explicit `depends_on` condition wins, as its edges come first
+                if !waits.iter().any(|(to, _)| *to == edge.to) {
+                    let condition = edge.condition.unwrap_or(DependsOnCondition::ServiceStarted);
+                    waits.push((edge.to.clone(), condition));
+                }
+            }
+            waits_for.push((service.clone(), waits));
+        }
+
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        let mut planned = 0;
+        while planned < self.services.len() {
+            let batch: Vec<String> = waits_for
+                .iter()
+                .filter(|(service, waits)| {
+                    !batches.iter().flatten().any(|s| s == service)
+                        && waits
+                            .iter()
+                            .all(|(to, _)| batches.iter().flatten().any(|s| s == to))
+                })
+                .map(|(service, _)| service.clone())
+                .collect();
+            // Unreachable without cycles, but never loop forever
+            if batch.is_empty() {
+                break;
+            }
+            planned += batch.len();
+            batches.push(batch);
+        }
+        Ok(StartupPlan { batches, waits_for })
+    }
+}
+
+/// The order in which Compose starts and stops the services of a manifest
+///
+/// Services start in batches: every service of a batch only waits for services of earlier
+/// batches, so the services of a batch can start in parallel. They stop in the reverse order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupPlan {
+    batches: Vec<Vec<String>>,
+    waits_for: Vec<(String, Vec<(String, DependsOnCondition)>)>,
+}
+
+impl StartupPlan {
+    /// The batches of services in the order they start
+    pub fn startup(&self) -> &[Vec<String>] {
+        &self.batches
+    }
+
+    /// The batches of services in the order they stop, the reverse of [`StartupPlan::startup`]
+    pub fn shutdown(&self) -> impl Iterator<Item = &[String]> {
+        self.batches.iter().rev().map(Vec::as_slice)
+    }
+
+    /// The services `service` waits for before starting, along with the condition each one
+    /// must meet
+    ///
+    /// Relationships other than `depends_on`, such as `links`, wait for the service to start.
+    pub fn waits_for(&self, service: &str) -> &[(String, DependsOnCondition)] {
+        self.waits_for
+            .iter()
+            .find(|(s, _)| s == service)
+            .map(|(_, waits)| waits.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// The references of the service `name` to other services, defined or not
+fn edges(name: &str, service: &Service) -> Vec<Edge> {
+    let path = pointer("/services", name);
+    let edge = |to: &str, kind, path: String| Edge {
+        from: name.to_owned(),
+        to: to.to_owned(),
+        kind,
+        path,
+        condition: None,
+        required: true,
+    };
+    let mut edges = Vec::new();
+
+    match &service.depends_on {
+        Some(DependsOn::List(services)) => {
+            for (i, to) in services.iter().enumerate() {
+                edges.push(Edge {
+                    condition: Some(DependsOnCondition::ServiceStarted),
+                    ..edge(
+                        to,
+                        EdgeKind::DependsOn,
+                        pointer(&pointer(&path, "depends_on"), i),
+                    )
+                });
+            }
+        }
+        Some(DependsOn::Map(services)) => {
+            for (to, detail) in services {
+                edges.push(Edge {
+                    condition: Some(
+                        detail
+                            .condition
+                            .unwrap_or(DependsOnCondition::ServiceStarted),
+                    ),
+                    required: detail.required.unwrap_or(true),
+                    ..edge(
+                        to,
+                        EdgeKind::DependsOn,
+                        pointer(&pointer(&path, "depends_on"), to),
+                    )
+                });
+            }
+        }
+        None => (),
+    }
+
+    // `SERVICE[:ALIAS]`
+    for (i, link) in service.links.iter().flatten().enumerate() {
+        let to = link.split(':').next().unwrap_or_default();
+        edges.push(edge(
+            to,
+            EdgeKind::Link,
+            pointer(&pointer(&path, "links"), i),
+        ));
+    }
+
+    // `SERVICE[:MODE]` or `container:NAME[:MODE]`
+    for (i, source) in service.volumes_from.iter().flatten().enumerate() {
+        let to = source.split(':').next().unwrap_or_default();
+        if to != "container" {
+            edges.push(edge(
+                to,
+                EdgeKind::VolumesFrom,
+                pointer(&pointer(&path, "volumes_from"), i),
+            ));
+        }
+    }
+
+    let modes = [
+        ("network_mode", &service.network_mode, EdgeKind::NetworkMode),
+        ("ipc", &service.ipc, EdgeKind::Ipc),
+    ];
+    for (field, mode, kind) in modes {
+        if let Some(to) = mode.as_deref().and_then(|m| m.strip_prefix("service:")) {
+            edges.push(edge(to, kind, pointer(&path, field)));
+        }
+    }
+
+    if let Some(Build::Map(build)) = &service.build {
+        let path = pointer(&pointer(&path, "build"), "additional_contexts");
+        let contexts: Vec<(String, &str)> = match &build.additional_contexts {
+            Some(AdditionalContexts::List(list)) => list
+                .iter()
+                .enumerate()
+                .filter_map(|(i, entry)| Some((i.to_string(), entry.split_once('=')?.1)))
+                .collect(),
+            Some(AdditionalContexts::Map(map)) => map
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_str()))
+                .collect(),
+            None => Vec::new(),
+        };
+        for (token, context) in contexts {
+            if let Some(to) = context.strip_prefix("service:") {
+                edges.push(edge(to, EdgeKind::BuildContext, pointer(&path, token)));
+            }
+        }
+    }
+
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edges() {
+        let yaml = r#"
+        services:
+          web:
+            build:
+              context: .
+              additional_contexts:
+                base: service:base
+                docs: https://github.com/example/docs.git
+            depends_on:
+              db:
+                condition: service_healthy
+              cache:
+                condition: service_started
+                required: false
+            links:
+              - db:database
+              - missing
+            volumes_from:
+              - data:ro
+              - container:legacy
+            ipc: service:db
+          proxy:
+            image: nginx
+            network_mode: service:web
+          db:
+            image: postgres
+          cache:
+            image: redis
+          data:
+            image: busybox
+          base:
+            image: base
+        "#;
+
+        let graph = Compose::new(yaml).unwrap().dependency_graph();
+        let edges: Vec<_> = graph
+            .edges()
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.kind, e.path.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (
+                    "web",
+                    "db",
+                    EdgeKind::DependsOn,
+                    "/services/web/depends_on/db"
+                ),
+                (
+                    "web",
+                    "cache",
+                    EdgeKind::DependsOn,
+                    "/services/web/depends_on/cache"
+                ),
+                ("web", "db", EdgeKind::Link, "/services/web/links/0"),
+                (
+                    "web",
+                    "data",
+                    EdgeKind::VolumesFrom,
+                    "/services/web/volumes_from/0"
+                ),
+                ("web", "db", EdgeKind::Ipc, "/services/web/ipc"),
+                (
+                    "web",
+                    "base",
+                    EdgeKind::BuildContext,
+                    "/services/web/build/additional_contexts/base"
+                ),
+                (
+                    "proxy",
+                    "web",
+                    EdgeKind::NetworkMode,
+                    "/services/proxy/network_mode"
+                ),
+            ]
+        );
+        let cache = &graph.edges()[1];
+        assert_eq!(cache.condition, Some(DependsOnCondition::ServiceStarted));
+        assert!(!cache.required);
+        assert_eq!(graph.dependents("web").count(), 1);
+        assert!(graph.cycles().is_empty());
+    }
+
+    #[test]
+    fn test_startup_plan() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            depends_on:
+              api:
+                condition: service_healthy
+              metrics:
+                condition: service_started
+                required: false
+          api:
+            image: api
+            depends_on:
+              db:
+                condition: service_healthy
+              migrate:
+                condition: service_completed_successfully
+          migrate:
+            image: migrate
+            depends_on: [db]
+          db:
+            image: postgres
+          metrics:
+            image: prometheus
+            profiles: [monitoring]
+          worker:
+            image: worker
+            links: [db]
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let plan = compose.startup_plan().unwrap();
+        assert_eq!(
+            plan.startup(),
+            [
+                vec!["db", "metrics"],
+                vec!["migrate", "worker"],
+                vec!["api"],
+                vec!["web"],
+            ]
+        );
+        assert_eq!(
+            plan.shutdown().collect::<Vec<_>>(),
+            [
+                &["web"][..],
+                &["api"],
+                &["migrate", "worker"],
+                &["db", "metrics"]
+            ]
+        );
+        assert_eq!(
+            plan.waits_for("api"),
+            [
+                ("db".to_owned(), DependsOnCondition::ServiceHealthy),
+                (
+                    "migrate".to_owned(),
+                    DependsOnCondition::ServiceCompletedSuccessfully
+                ),
+            ]
+        );
+        assert_eq!(
+            plan.waits_for("worker"),
+            [("db".to_owned(), DependsOnCondition::ServiceStarted)]
+        );
+
+        // `metrics` is disabled, and `web` does not require it
+        let plan = compose.with_profiles(&[]).unwrap().startup_plan().unwrap();
+        assert_eq!(
+            plan.startup(),
+            [
+                vec!["db"],
+                vec!["migrate", "worker"],
+                vec!["api"],
+                vec!["web"]
+            ]
+        );
+        assert!(plan.waits_for("web").iter().all(|(s, _)| s != "metrics"));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let yaml = r#"
+        services:
+          a:
+            image: a
+            depends_on: [b]
+          b:
+            image: b
+            volumes_from: [c]
+          c:
+            image: c
+            network_mode: service:a
+          d:
+            image: d
+            links: [d]
+            ipc: service:d
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let reported: Vec<_> = errors
+            .with_code(ErrorCode::DependencyCycle)
+            .map(|e| (e.path.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "/services/c/network_mode",
+                    "Dependency cycle a -> b -> c -> a"
+                ),
+                (
+                    "/services/d/links/0",
+                    "Service d depends on itself through links"
+                ),
+            ]
+        );
+        let error = errors.with_code(ErrorCode::DependencyCycle).next().unwrap();
+        assert_eq!(error.span.unwrap().start.line, 11);
+    }
+
+    #[test]
+    fn test_overlapping_cycles() {
+        let yaml = r#"
+        services:
+          a:
+            image: a
+            depends_on: [b, c]
+          b:
+            image: b
+            links: [a]
+          c:
+            image: c
+            depends_on: [b]
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let reported: Vec<_> = errors
+            .with_code(ErrorCode::DependencyCycle)
+            .map(|e| (e.path.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("/services/b/links/0", "Dependency cycle a -> b -> a"),
+                ("/services/b/links/0", "Dependency cycle a -> c -> b -> a"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,533 @@
+//! Include fields and loading
+//!
+//! Included files are loaded through a [`FileLoader`] when building a [`Compose`] with
+//! [`ComposeBuilder::loader`](crate::compose::ComposeBuilder::loader). Each included file is
+//! an independent Compose file, which may itself include others. Its resources are added to
+//! the including manifest, and redefining a resource that is already defined is an error.
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    interpolation::{self, DotEnv, VariableSource},
+    loader::{self, FileLoader},
+    merge, merge_keys,
+    span::{key_token, pointer, SourceMap},
+};
+
+#[cfg(doc)]
+use crate::Compose;
+
+/// Represents an entry of the top level [include](https://docs.docker.com/compose/compose-file/14-include/) element
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
+pub enum Include {
+    Short(String),
+    Long(IncludeDetails),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
+pub struct IncludeDetails {
+    /// Files merged in order into the included manifest
+    pub path: Paths,
+
+    /// The directory that relative paths within the included files, such as build contexts
+    /// and bind mounts, are relative to. Defaults to the directory of the first file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_directory: Option<String>,
+
+    /// Files with the variables used to interpolate the included manifest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Paths>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
+pub enum Paths {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl Paths {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Paths::Single(path) => vec![path.clone()],
+            Paths::List(paths) => paths.clone(),
+        }
+    }
+}
+
+/// The top level elements whose entries may not be redefined by an included file
+const RESOURCES: [(&str, &str); 5] = [
+    ("services", "Service"),
+    ("networks", "Network"),
+    ("volumes", "Volume"),
+    ("configs", "Config"),
+    ("secrets", "Secret"),
+];
+
+/// Load the files included by `document` and add their resources to it
+///
+/// `file` is the name of the root manifest, if known, so that files including it again are
+/// reported as circular.
+pub(crate) fn resolve(
+    document: &mut Value,
+    file: Option<&str>,
+    loader: &dyn FileLoader,
+    variables: Option<&dyn VariableSource>,
+    errors: &mut ValidationErrors,
+) {
+    let includer = Includer { loader, variables };
+    let mut stack: Vec<String> = file
+        .map(|file| loader::join(None, file))
+        .into_iter()
+        .collect();
+    includer.expand(document, file, &mut stack, None, errors);
+}
+
+struct Includer<'a> {
+    loader: &'a dyn FileLoader,
+    variables: Option<&'a dyn VariableSource>,
+}
+
+/// The variables the manifest is built with, falling back to those from `env_file`
+///
+/// As with Compose, where the environment overrides `.env` files, `env_file` only provides
+/// defaults. When several files define a variable, the last one wins.
+struct IncludeVariables<'a> {
+    fallback: Option<&'a dyn VariableSource>,
+    files: Vec<DotEnv>,
+}
+
+impl VariableSource for IncludeVariables<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        self.fallback
+            .and_then(|f| f.get(name))
+            .or_else(|| self.files.iter().rev().find_map(|f| f.get(name)))
+    }
+}
+
+impl Includer<'_> {
+    /// Add the resources of every file included by `document`, which was loaded from `file`
+    ///
+    /// `origin` is the path of the root include entry that led to `document`, which is where
+    /// errors in nested files are reported.
+    fn expand(
+        &self,
+        document: &mut Value,
+        file: Option<&str>,
+        stack: &mut Vec<String>,
+        origin: Option<&str>,
+        errors: &mut ValidationErrors,
+    ) {
+        let Some(entries) = document
+            .get("include")
+            .and_then(Value::as_sequence)
+            .cloned()
+        else {
+            return;
+        };
+        for (index, entry) in entries.into_iter().enumerate() {
+            let path = origin.map_or_else(|| pointer("/include", index), str::to_owned);
+            let include = match serde_yaml::from_value::<Include>(entry) {
+                Ok(include) => include,
+                Err(e) => {
+                    errors.add_error(ValidationError::new(
+                        ErrorCode::InvalidInclude,
+                        &path,
+                        e.to_string(),
+                    ));
+                    continue;
+                }
+            };
+            match self.load(&include, file, stack, &path) {
+                Ok((name, included)) => add(document, included, &name, &path, errors),
+                Err(e) => errors.extend(e),
+            }
+        }
+    }
+
+    /// Load, interpolate and merge the files of `include`, with their own includes expanded
+    ///
+    /// Returns a description of the files along with the merged manifest.
+    fn load(
+        &self,
+        include: &Include,
+        file: Option<&str>,
+        stack: &mut Vec<String>,
+        path: &str,
+    ) -> Result<(String, Value), ValidationErrors> {
+        let (paths, env_files, project_directory) = match include {
+            Include::Short(path) => (vec![path.clone()], Vec::new(), None),
+            Include::Long(details) => (
+                details.path.to_vec(),
+                details
+                    .env_file
+                    .as_ref()
+                    .map(Paths::to_vec)
+                    .unwrap_or_default(),
+                details.project_directory.as_deref(),
+            ),
+        };
+        let project_directory = match project_directory {
+            Some(directory) => loader::join(file, directory),
+            None => paths
+                .first()
+                .map(|first| loader::directory(&loader::join(file, first)))
+                .unwrap_or_default(),
+        };
+
+        let mut variables = IncludeVariables {
+            fallback: self.variables,
+            files: Vec::new(),
+        };
+        for env_file in env_files {
+            let contents = self.read(&loader::join(file, &env_file), path)?;
+            variables.files.push(DotEnv::parse(&contents));
+        }
+
+        let mut names = Vec::new();
+        let mut merged: Option<Value> = None;
+        for included in paths {
+            let name = loader::join(file, &included);
+            if stack.contains(&name) {
+                let mut cycle = stack.clone();
+                cycle.push(name);
+                return Err(ValidationError::new(
+                    ErrorCode::InvalidInclude,
+                    path,
+                    format!("Circular include: {}", cycle.join(" -> ")),
+                )
+                .into());
+            }
+
+            let contents = self.read(&name, path)?;
+            let mut value: Value = serde_yaml::from_str(&contents).map_err(|e| {
+                ValidationErrors::from(
+                    ValidationError::new(ErrorCode::InvalidCompose, path, format!("{name}: {e}"))
+                        .with_value(&name),
+                )
+            })?;
+
+            let mut problems = ValidationErrors::new();
+            merge_keys::apply(&mut value, "", &mut problems);
+            if self.variables.is_some() || !variables.files.is_empty() {
+                let source = SourceMap::for_document(&contents, 0);
+                interpolation::interpolate(&mut value, &variables, &source, "", &mut problems);
+            }
+            if problems.has_errors() {
+                // Errors within the included file are reported at the include entry
+                let mut errors = ValidationErrors::new();
+                errors.extend(problems.into_iter().map(|e| e.nested(&name, path)));
+                return Err(errors);
+            }
+            rebase(&mut value, &project_directory);
+
+            let mut problems = ValidationErrors::new();
+            stack.push(name.clone());
+            self.expand(&mut value, Some(&name), stack, Some(path), &mut problems);
+            stack.pop();
+            if problems.has_errors() {
+                return Err(problems);
+            }
+
+            match &mut merged {
+                Some(base) => merge::merge(base, value),
+                None => merged = Some(value),
+            }
+            names.push(name);
+        }
+
+        Ok((names.join(", "), merged.unwrap_or_default()))
+    }
+
+    fn read(&self, name: &str, path: &str) -> Result<String, ValidationErrors> {
+        self.loader.load(name).map_err(|e| {
+            ValidationError::new(
+                ErrorCode::MissingFile,
+                path,
+                format!("Cannot load {name}: {e}"),
+            )
+            .with_value(name)
+            .into()
+        })
+    }
+}
+
+/// Make the relative paths of the included manifest `value` relative to the root manifest,
+/// from the `directory` they are relative to
+///
+/// The resources of files it includes in turn are added afterwards, already rebased.
+fn rebase(value: &mut Value, directory: &str) {
+    if let Some(Value::Mapping(services)) = value.get_mut("services") {
+        for (_, service) in services.iter_mut() {
+            loader::rebase_service(service, directory);
+        }
+    }
+    for section in ["configs", "secrets"] {
+        if let Some(Value::Mapping(resources)) = value.get_mut(section) {
+            for (_, resource) in resources.iter_mut() {
+                if let Some(file) = resource.get_mut("file") {
+                    loader::rebase_path(file, directory);
+                }
+            }
+        }
+    }
+}
+
+/// Add the resources of `included` to `document`, reporting any that are already defined
+fn add(
+    document: &mut Value,
+    included: Value,
+    name: &str,
+    path: &str,
+    errors: &mut ValidationErrors,
+) {
+    let Value::Mapping(mut included) = included else {
+        return;
+    };
+    for (section, kind) in RESOURCES {
+        let Some(Value::Mapping(resources)) = included.remove(section) else {
+            continue;
+        };
+        let Some(document) = document.as_mapping_mut() else {
+            return;
+        };
+        let existing = document
+            .entry(section.into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if existing.is_null() {
+            *existing = Value::Mapping(Mapping::new());
+        }
+        let Some(existing) = existing.as_mapping_mut() else {
+            continue;
+        };
+        for (key, resource) in resources {
+            if existing.contains_key(&key) {
+                let key = key_token(&key);
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::ConflictingInclude,
+                        path,
+                        format!("{kind} {key} from {name} is already defined"),
+                    )
+                    .with_value(key),
+                );
+            } else {
+                existing.insert(key, resource);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compose;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_deserialize_include() {
+        let yaml = r#"
+        - common.yml
+        - path:
+            - db.yml
+            - db.override.yml
+          project_directory: ./db
+          env_file: ./db/.env
+        "#;
+
+        let include: Vec<Include> = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(&include[0], Include::Short(path) if path == "common.yml"));
+        let Include::Long(details) = &include[1] else {
+            panic!("expected the long syntax");
+        };
+        assert_eq!(details.path.to_vec(), ["db.yml", "db.override.yml"]);
+        assert_eq!(details.project_directory.as_deref(), Some("./db"));
+    }
+
+    #[test]
+    fn test_include() {
+        let yaml = r#"
+        include:
+          - path: db/compose.yml
+            env_file: db/.env
+        services:
+          web:
+            image: web
+            depends_on:
+              - db
+        "#;
+        let files = HashMap::from([
+            (
+                "db/compose.yml".to_owned(),
+                r#"
+                include:
+                  - ../cache.yml
+                services:
+                  db:
+                    image: postgres:${POSTGRES_VERSION}
+                volumes:
+                  data:
+                "#
+                .to_owned(),
+            ),
+            ("db/.env".to_owned(), "POSTGRES_VERSION=16".to_owned()),
+            (
+                "cache.yml".to_owned(),
+                "services:\n  cache:\n    image: redis\n".to_owned(),
+            ),
+        ]);
+
+        let compose = Compose::builder()
+            .document(yaml)
+            .loader(&files)
+            .build()
+            .unwrap();
+        assert_eq!(compose.services.len(), 3);
+        assert_eq!(compose.services["db"].image.as_deref(), Some("postgres:16"));
+        assert!(compose.volumes.unwrap().contains_key("data"));
+        assert_eq!(compose.include.unwrap().len(), 1);
+
+        // The variables the manifest is built with take precedence over env_file
+        let variables = HashMap::from([("POSTGRES_VERSION".to_owned(), "17".to_owned())]);
+        let compose = Compose::builder()
+            .document(yaml)
+            .loader(&files)
+            .variables(&variables)
+            .build()
+            .unwrap();
+        assert_eq!(compose.services["db"].image.as_deref(), Some("postgres:17"));
+    }
+
+    #[test]
+    fn test_include_conflict() {
+        let yaml = r#"
+        include:
+          - web.yml
+        services:
+          web:
+            image: web
+        "#;
+        let files = HashMap::from([
+            (
+                "web.yml".to_owned(),
+                "include: [other.yml]\nservices:\n  web:\n    image: web:2\n".to_owned(),
+            ),
+            ("other.yml".to_owned(), "include: [web.yml]\n".to_owned()),
+        ]);
+
+        let errors = Compose::builder()
+            .document(yaml)
+            .loader(&files)
+            .build()
+            .unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::InvalidInclude);
+        assert_eq!(error.path, "/include/0");
+        assert_eq!(
+            error.message,
+            "Circular include: web.yml -> other.yml -> web.yml"
+        );
+
+        let files = HashMap::from([(
+            "web.yml".to_owned(),
+            "services:\n  web:\n    image: web:2\n".to_owned(),
+        )]);
+        let errors = Compose::builder()
+            .document(yaml)
+            .loader(&files)
+            .build()
+            .unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::ConflictingInclude);
+        assert_eq!(error.message, "Service web from web.yml is already defined");
+        assert_eq!(error.span.unwrap().start.line, 3);
+    }
+
+    #[test]
+    fn test_include_project_directory() {
+        let yaml = r#"
+        include:
+          - db/compose.yml
+          - path: cache/compose.yml
+            project_directory: cache/project
+        "#;
+        let files = HashMap::from([
+            (
+                "db/compose.yml".to_owned(),
+                r#"
+                services:
+                  db:
+                    build: ./image
+                    volumes:
+                      - ./data:/var/lib/postgresql/data
+                "#
+                .to_owned(),
+            ),
+            (
+                "cache/compose.yml".to_owned(),
+                r#"
+                services:
+                  cache:
+                    build:
+                      context: .
+                    env_file:
+                      - ../.env
+                    volumes:
+                      - /etc/redis:/etc/redis
+                "#
+                .to_owned(),
+            ),
+        ]);
+
+        let compose = Compose::builder()
+            .document(yaml)
+            .loader(&files)
+            .build()
+            .unwrap();
+        let value = serde_yaml::to_value(&compose.services).unwrap();
+        assert_eq!(value["db"]["build"], "./db/image");
+        assert_eq!(
+            value["db"]["volumes"][0],
+            "./db/data:/var/lib/postgresql/data"
+        );
+        assert_eq!(value["cache"]["build"]["context"], "./cache/project");
+        assert_eq!(value["cache"]["env_file"][0], "./cache/.env");
+        assert_eq!(value["cache"]["volumes"][0], "/etc/redis:/etc/redis");
+    }
+
+    #[test]
+    fn test_include_root() {
+        let yaml = r#"
+        include:
+          - web.yml
+        services:
+          db:
+            image: postgres
+        "#;
+        let files = HashMap::from([(
+            "web.yml".to_owned(),
+            "include: [compose.yaml]\nservices:\n  web:\n    image: web\n".to_owned(),
+        )]);
+
+        let errors = Compose::builder()
+            .document(yaml)
+            .file_name("compose.yaml")
+            .loader(&files)
+            .build()
+            .unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::InvalidInclude);
+        assert_eq!(error.path, "/include/0");
+        assert_eq!(
+            error.message,
+            "Circular include: compose.yaml -> web.yml -> compose.yaml"
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! Variable interpolation for Compose manifests
+//!
+//! Supports the [interpolation syntax](https://docs.docker.com/compose/compose-file/12-interpolation/)
+//! of the Compose spec: `$VAR`, `${VAR}`, `${VAR:-default}`, `${VAR-default}`, `${VAR:?error}`,
+//! `${VAR?error}`, `${VAR:+replacement}`, `${VAR+replacement}` and `$$` escapes. Defaults and
+//! replacements may themselves contain interpolations.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use serde_yaml::Value;
+
+use crate::{
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    span::{key_token, pointer, NodeKind, SourceMap},
+};
+
+/// Provides the values of variables referenced in a manifest
+pub trait VariableSource {
+    /// Look up a variable, returning `None` if it is unset
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+impl<S: BuildHasher> VariableSource for HashMap<String, String, S> {
+    fn get(&self, name: &str) -> Option<String> {
+        HashMap::get(self, name).cloned()
+    }
+}
+
+/// Reads variables from the environment of the current process
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessEnvironment;
+
+impl VariableSource for ProcessEnvironment {
+    fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+}
+
+/// Variables parsed from the contents of a `.env` file
+#[derive(Debug, Clone, Default)]
+pub struct DotEnv {
+    variables: HashMap<String, String>,
+}
+
+impl DotEnv {
+    /// Parse the contents of a `.env` file
+    ///
+    /// Blank lines, `#` comments and an optional `export ` prefix are ignored. Single quoted
+    /// values are taken literally, double quoted values support `\n`, `\t`, `\"` and `\\`
+    /// escapes, and unquoted values are trimmed and end at an inline ` #` comment.
+    pub fn parse(contents: &str) -> Self {
+        let variables = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let line = line.strip_prefix("export ").unwrap_or(line);
+                let (key, value) = line.split_once('=')?;
+                Some((key.trim().to_owned(), Self::parse_value(value.trim())))
+            })
+            .collect();
+        DotEnv { variables }
+    }
+
+    fn parse_value(value: &str) -> String {
+        if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+            return inner.to_owned();
+        }
+        if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            let mut result = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some(next @ ('n' | 't' | '"' | '\\'))) => {
+                        chars.next();
+                        result.push(match next {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => other,
+                        });
+                    }
+                    (c, _) => result.push(c),
+                }
+            }
+            return result;
+        }
+        match value.find(" #") {
+            Some(i) => value[..i].trim_end().to_owned(),
+            None => value.to_owned(),
+        }
+    }
+}
+
+impl VariableSource for DotEnv {
+    fn get(&self, name: &str) -> Option<String> {
+        self.variables.get(name).cloned()
+    }
+}
+
+/// Why a string could not be interpolated
+#[derive(Debug, PartialEq)]
+enum Problem {
+    /// A `${VAR:?message}` or `${VAR?message}` variable has no value
+    Required { name: String, message: String },
+    /// The string is not valid interpolation syntax
+    Invalid(String),
+}
+
+/// Interpolate every string value within `value`, which is located at `path`
+///
+/// Mapping keys are not interpolated. Interpolated values stay strings, except that a plain
+/// (unquoted) scalar in `source` that interpolates into a number or boolean is replaced with
+/// that typed value, just as if it had been written literally. This keeps fields such as
+/// `cpu_count: ${CPUS}` parsing, while `"${PORT}"` remains a string.
+pub(crate) fn interpolate(
+    value: &mut Value,
+    variables: &dyn VariableSource,
+    source: &SourceMap,
+    path: &str,
+    errors: &mut ValidationErrors,
+) {
+    match value {
+        Value::String(s) if s.contains('$') => match substitute(s, variables) {
+            Ok(result) => {
+                let plain = matches!(
+                    source.origin(path).map(|node| node.kind),
+                    Some(NodeKind::Scalar { quote: None })
+                );
+                *value = if plain {
+                    typed(result)
+                } else {
+                    Value::String(result)
+                };
+            }
+            Err(Problem::Required { name, message }) => errors.add_error(
+                ValidationError::new(ErrorCode::MissingVariable, path, message).with_value(name),
+            ),
+            Err(Problem::Invalid(message)) => errors.add_error(
+                ValidationError::new(ErrorCode::InvalidInterpolation, path, message)
+                    .with_value(s.as_str()),
+            ),
+        },
+        Value::Sequence(seq) => seq
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, v)| interpolate(v, variables, source, &pointer(path, i), errors)),
+        Value::Mapping(map) => map.iter_mut().for_each(|(k, v)| {
+            interpolate(v, variables, source, &pointer(path, key_token(k)), errors)
+        }),
+        Value::Tagged(tagged) => interpolate(&mut tagged.value, variables, source, path, errors),
+        _ => (),
+    }
+}
+
+/// Convert an interpolated string back into a number or boolean if it reads as one
+fn typed(result: String) -> Value {
+    match serde_yaml::from_str::<Value>(&result) {
+        Ok(Value::Number(n)) if n.to_string() == result => Value::Number(n),
+        Ok(Value::Bool(b)) if b.to_string() == result => Value::Bool(b),
+        _ => Value::String(result),
+    }
+}
+
+fn substitute(input: &str, variables: &dyn VariableSource) -> Result<String, Problem> {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = closing_brace(after)
+                .ok_or_else(|| Problem::Invalid(format!("Unclosed `${{` in: {input}")))?;
+            result.push_str(&braced(&after[..end], variables)?);
+            rest = &after[end + 1..];
+        } else {
+            let len = name_len(rest);
+            if len == 0 {
+                result.push('$');
+            } else {
+                result.push_str(&variables.get(&rest[..len]).unwrap_or_default());
+                rest = &rest[len..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Resolve the contents of a `${...}` expression
+fn braced(expression: &str, variables: &dyn VariableSource) -> Result<String, Problem> {
+    let len = name_len(expression);
+    if len == 0 {
+        return Err(Problem::Invalid(format!(
+            "Invalid variable name in: ${{{expression}}}"
+        )));
+    }
+    let name = &expression[..len];
+    let value = variables.get(name);
+    let rest = &expression[len..];
+    let operator_len = if rest.starts_with(':') { 2 } else { 1 }.min(rest.len());
+    let (operator, word) = match (rest.get(..operator_len), rest.get(operator_len..)) {
+        (Some(operator), Some(word)) => (operator, word),
+        _ => (rest, ""),
+    };
+
+    let unset = value.is_none();
+    let empty = value.as_deref().is_none_or(str::is_empty);
+    match operator {
+        "" => Ok(value.unwrap_or_default()),
+        ":-" | "-" => {
+            if (operator == ":-" && empty) || unset {
+                substitute(word, variables)
+            } else {
+                Ok(value.unwrap_or_default())
+            }
+        }
+        ":?" | "?" => {
+            if (operator == ":?" && empty) || unset {
+                let message = substitute(word, variables)?;
+                Err(Problem::Required {
+                    name: name.to_owned(),
+                    message: if message.is_empty() {
+                        format!("Required variable {name} is missing a value")
+                    } else {
+                        message
+                    },
+                })
+            } else {
+                Ok(value.unwrap_or_default())
+            }
+        }
+        ":+" | "+" => {
+            if (operator == ":+" && empty) || unset {
+                Ok(String::new())
+            } else {
+                substitute(word, variables)
+            }
+        }
+        _ => Err(Problem::Invalid(format!(
+            "Invalid interpolation format: ${{{expression}}}"
+        ))),
+    }
+}
+
+/// Length of the variable name at the start of `s`
+fn name_len(s: &str) -> usize {
+    let mut chars = s.char_indices();
+    match chars.next() {
+        Some((_, c)) if c == '_' || c.is_ascii_alphabetic() => chars
+            .find(|(_, c)| !(*c == '_' || c.is_ascii_alphanumeric()))
+            .map_or(s.len(), |(i, _)| i),
+        _ => 0,
+    }
+}
+
+/// Index of the `}` closing a `${`, accounting for nested expressions and literal braces
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compose;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("TAG".to_owned(), "1.2".to_owned()),
+            ("EMPTY".to_owned(), String::new()),
+            ("PORT".to_owned(), "8080".to_owned()),
+        ])
+    }
+
+    #[test]
+    fn test_substitute() {
+        let vars = variables();
+        let cases = [
+            ("nginx:$TAG", "nginx:1.2"),
+            ("nginx:${TAG}", "nginx:1.2"),
+            ("${MISSING:-latest}", "latest"),
+            ("${EMPTY:-latest}", "latest"),
+            ("${EMPTY-latest}", ""),
+            ("${MISSING-latest}", "latest"),
+            ("${TAG:+set}", "set"),
+            ("${EMPTY:+set}", ""),
+            ("${EMPTY+set}", "set"),
+            ("${MISSING:-${ALSO_MISSING:-${TAG}}}", "1.2"),
+            ("echo $$HOME", "echo $HOME"),
+            ("$MISSING", ""),
+            ("cost: 5$", "cost: 5$"),
+            ("${MISSING:-{a}}", "{a}"),
+            ("${MISSING:-{\"a\": {}}}!", "{\"a\": {}}!"),
+            ("${TAG:-{a}}", "1.2"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(substitute(input, &vars).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_substitute_errors() {
+        let vars = variables();
+        assert_eq!(
+            substitute("${DB_PASS:?must be set}", &vars),
+            Err(Problem::Required {
+                name: "DB_PASS".to_owned(),
+                message: "must be set".to_owned()
+            })
+        );
+        assert!(substitute("${EMPTY?must be set}", &vars).is_ok());
+        assert!(matches!(
+            substitute("${TAG", &vars),
+            Err(Problem::Invalid(_))
+        ));
+        assert!(matches!(
+            substitute("${1TAG}", &vars),
+            Err(Problem::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_dotenv() {
+        let env = DotEnv::parse(
+            r#"
+            # comment
+            export TAG=1.2
+            QUOTED="a\nb"
+            LITERAL='$HOME'
+            INLINE=value # comment
+            "#,
+        );
+        assert_eq!(env.get("TAG").as_deref(), Some("1.2"));
+        assert_eq!(env.get("QUOTED").as_deref(), Some("a\nb"));
+        assert_eq!(env.get("LITERAL").as_deref(), Some("$HOME"));
+        assert_eq!(env.get("INLINE").as_deref(), Some("value"));
+    }
+
+    #[test]
+    fn test_interpolated_compose() {
+        let yaml = r#"
+        services:
+          web:
+            image: "nginx:${TAG:-latest}"
+            cpu_count: ${CPUS:-2}
+            expose:
+              - $PORT
+        "#;
+
+        let compose = Compose::with_variables(yaml, &variables()).unwrap();
+        let web = &compose.services["web"];
+        assert_eq!(web.image.as_deref(), Some("nginx:1.2"));
+        assert_eq!(web.cpu_count, Some(2));
+        assert_eq!(web.expose.as_ref().unwrap()[0], "8080");
+    }
+
+    #[test]
+    fn test_missing_required_variable() {
+        let yaml = r#"
+        services:
+          db:
+            image: postgres
+            environment:
+              POSTGRES_PASSWORD: ${DB_PASS:?must be set}
+        "#;
+
+        let errors = Compose::with_variables(yaml, &variables()).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::MissingVariable);
+        assert_eq!(error.path, "/services/db/environment/POSTGRES_PASSWORD");
+        assert_eq!(error.message, "must be set");
+        assert_eq!(error.span.unwrap().start.line, 6);
+    }
+
+    #[test]
+    fn test_interpolated_type_error_is_located() {
+        let yaml = r#"
+        services:
+          web:
+            image: nginx
+            cpu_count: ${TAG}
+        "#;
+
+        let errors = Compose::with_variables(yaml, &variables()).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::InvalidCompose);
+        assert_eq!(error.path, "/services/web/cpu_count");
+        assert_eq!(error.span.unwrap().start.line, 5);
+    }
+
+    #[test]
+    fn test_merge_key_values_are_typed() {
+        let yaml = r#"
+        x-defaults: &defaults
+          cpu_count: ${CPUS}
+          environment: &environment
+            PORT: "${PORT}"
+        services:
+          web:
+            <<: *defaults
+            image: nginx
+          api:
+            <<: [{hostname: api}, *defaults]
+            image: api
+            labels: *environment
+        "#;
+
+        let mut vars = variables();
+        vars.insert("CPUS".to_owned(), "2".to_owned());
+        let compose = Compose::with_variables(yaml, &vars).unwrap();
+        for name in ["web", "api"] {
+            let service = &compose.services[name];
+            assert_eq!(service.cpu_count, Some(2));
+            assert!(matches!(
+                service.environment.as_ref().unwrap(),
+                crate::services::Labels::Map(env) if env["PORT"] == "8080"
+            ));
+        }
+        assert!(matches!(
+            compose.services["api"].labels.as_ref().unwrap(),
+            crate::services::Labels::Map(labels) if labels["PORT"] == "8080"
+        ));
+    }
+
+    #[test]
+    fn test_quoted_values_stay_strings() {
+        let yaml = r#"
+        services:
+          web:
+            image: nginx
+            ports:
+              - "${PORT}"
+            environment:
+              PORT: "${PORT}"
+            labels:
+              enabled: '${FLAG}'
+        "#;
+
+        let mut vars = variables();
+        vars.insert("FLAG".to_owned(), "true".to_owned());
+        let compose = Compose::with_variables(yaml, &vars).unwrap();
+        let web = &compose.services["web"];
+        assert!(matches!(
+            web.environment.as_ref().unwrap(),
+            crate::services::Labels::Map(env) if env["PORT"] == "8080"
+        ));
+        assert!(matches!(
+            web.labels.as_ref().unwrap(),
+            crate::services::Labels::Map(labels) if labels["enabled"] == "true"
+        ));
+        assert!(matches!(
+            &web.ports.as_ref().unwrap()[0],
+            crate::services::ports::Ports::Short(port) if port == "8080"
+        ));
+    }
+}
@@ -9,9 +9,40 @@
 //!
 //! # High-level Features
 //!
-//! - Create and validate a Docker Compose structure from `&str`
-//! - Access the fields for a Compose manifest
-//! - View multiple validation errors at once
+//! - Create and validate a Docker Compose structure from YAML or JSON, and write it back out
+//!   in either format
+//! - Interpolate `${VARIABLE}` references from a pluggable variable source
+//! - Resolve YAML anchors and `<<` merge keys
+//! - Merge multiple documents, such as `compose.override.yaml`, following the Compose spec
+//!   merge rules
+//! - Load the files of the top level `include` element, reporting resources they redefine
+//! - Normalize short syntax, such as `"8080:80"` ports, into the canonical long syntax
+//! - Select the services enabled by a set of profiles
+//! - Resolve `extends` into the effective services, loading other files through a pluggable
+//!   loader
+//! - Edit a manifest programmatically while keeping its comments, quoting and layout
+//! - Access the fields for a Compose manifest, including `x-` extension fields, in source order
+//! - Check documents against the compose-spec JSON Schema, with the `compose-spec` feature
+//! - Generate a JSON Schema and TypeScript definitions of the model, with the `typegen` feature
+//! - Report unknown keys, such as a misspelled `enviroment`, as errors or warnings with the
+//!   nearest known field as a suggestion
+//! - View multiple validation errors at once, each with a stable error code, the path of the
+//!   offending node and the line and column it occurred at
+//! - Flag deprecated fields, such as `mem_limit` or the top level `version`, as warnings that
+//!   are returned with the manifest instead of failing the build
+//! - Configure lint rules, enabling, disabling or changing the severity of the built-in ones
+//!   and registering your own
+//! - Opt in to security rules for risky settings such as `privileged: true`, `network_mode: host`
+//!   or mounting the Docker socket
+//! - Flag credentials hard-coded in `environment`, `build.args` and `labels`, which belong in
+//!   the top level `secrets` element
+//! - Build the dependency graph of the services from `depends_on`, `links`, `volumes_from`,
+//!   `network_mode`, `ipc` and `build.additional_contexts`, reporting cycles
+//! - Plan the order in which services start and stop, in batches that can start in parallel
+//! - Render the topology of a manifest as a Graphviz DOT or Mermaid diagram
+//! - Report host ports published more than once on the same address and protocol
+//! - Parse the short port syntax, such as `127.0.0.1:8080-8081:80-81/udp`, into a
+//!   [`PortMapping`](services::ports::PortMapping) and convert it to and from the long syntax
 //!  
 //! # Purpose
 //!
@@ -161,7 +192,9 @@
 //!   }
 //!   Err(errors) => {
 //!     // Compose had one or many errors
-//!     ()
+//!     for error in errors.under("/services/gitlab") {
+//!       println!("{}: {}", error.code, error.message);
+//!     }
 //!   }
 //! }
 //! ```
@@ -169,19 +202,36 @@
 #![warn(
     clippy::all,
     clippy::todo,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::inefficient_to_string,
-    clippy::str_to_string,
-    clippy::str_to_string,
-    clippy::missing_docs
+    clippy::str_to_string
 )]
 #![deny(unreachable_pub, unreachable_code, unsafe_code)]
 pub mod compose;
 pub mod configs;
+pub mod document;
 pub mod errors;
+mod extends;
+pub mod extensions;
+pub mod graph;
+pub mod include;
+pub mod interpolation;
+pub mod loader;
+mod merge;
+mod merge_keys;
 pub mod networks;
+mod render;
+pub mod rules;
+#[cfg(feature = "compose-spec")]
+mod schema;
 pub mod secrets;
 pub mod services;
+pub mod span;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "typegen")]
+pub mod typegen;
+pub mod unknown_keys;
 pub mod volumes;
 
 pub use crate::compose::Compose;
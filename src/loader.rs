@@ -0,0 +1,168 @@
+//! Loading of files referenced by a manifest, such as `extends.file` and `include`
+//!
+//! Validation never touches the host on its own: referenced files are read through a
+//! [`FileLoader`], which can be backed by the file system or by files held in memory.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde_yaml::Value;
+
+/// Provides the contents of files referenced by a manifest
+pub trait FileLoader {
+    /// Load the file at `path`, which is relative to the directory of the root manifest
+    fn load(&self, path: &str) -> io::Result<String>;
+}
+
+impl<S: BuildHasher> FileLoader for HashMap<String, String, S> {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.get(path).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{path} does not exist"))
+        })
+    }
+}
+
+/// Reads files from the file system, relative to a root directory
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    root: PathBuf,
+}
+
+impl FileSystem {
+    /// Read files relative to `root`, usually the directory of the root manifest
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileSystem { root: root.into() }
+    }
+}
+
+impl FileLoader for FileSystem {
+    fn load(&self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.root.join(path))
+    }
+}
+
+/// Resolve `path` relative to the directory of the file at `base`
+///
+/// `base` is `None` for the root manifest. `.` and `..` components are resolved lexically,
+/// so that the same file is always known by the same path.
+pub(crate) fn join(base: Option<&str>, path: &str) -> String {
+    normalize(&Path::new(&directory(base.unwrap_or_default())).join(path))
+}
+
+/// The directory of the file at `file`, relative to the directory of the root manifest
+pub(crate) fn directory(file: &str) -> String {
+    Path::new(file)
+        .parent()
+        .map(|parent| parent.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Resolve `.` and `..` components of `path` lexically
+fn normalize(path: &Path) -> String {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved.to_string_lossy().into_owned()
+}
+
+/// Make the relative paths of `service`, which are relative to `directory`, relative to the
+/// root manifest instead
+///
+/// This covers the build context, the sources of bind mounts and the `env_file` entries.
+pub(crate) fn rebase_service(service: &mut Value, directory: &str) {
+    let rebase_path = |path: &mut Value| rebase_path(path, directory);
+
+    match service.get_mut("build") {
+        Some(build @ Value::String(_)) => rebase_path(build),
+        Some(Value::Mapping(build)) => build.get_mut("context").into_iter().for_each(rebase_path),
+        _ => (),
+    }
+
+    if let Some(Value::Sequence(volumes)) = service.get_mut("volumes") {
+        for volume in volumes {
+            match volume {
+                Value::String(short) => {
+                    let (source, rest) = short.split_once(':').unwrap_or((short, ""));
+                    if source.starts_with('.') {
+                        if let Some(source) = rebased(directory, source) {
+                            *short = match rest {
+                                "" => source,
+                                rest => format!("{source}:{rest}"),
+                            };
+                        }
+                    }
+                }
+                Value::Mapping(long)
+                    if long.get("type").and_then(Value::as_str) == Some("bind") =>
+                {
+                    long.get_mut("source").into_iter().for_each(rebase_path)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    match service.get_mut("env_file") {
+        Some(env_file @ Value::String(_)) => rebase_path(env_file),
+        Some(Value::Sequence(files)) => {
+            for entry in files {
+                match entry {
+                    Value::Mapping(entry) => {
+                        entry.get_mut("path").into_iter().for_each(rebase_path)
+                    }
+                    entry => rebase_path(entry),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Make `path`, if it is a string relative to `directory`, relative to the root manifest
+pub(crate) fn rebase_path(path: &mut Value, directory: &str) {
+    if let Value::String(p) = path {
+        if let Some(rebased) = rebased(directory, p) {
+            *p = rebased;
+        }
+    }
+}
+
+/// `path` relative to the root manifest instead of to `directory`, unless it is not relative
+///
+/// Absolute paths, home directory paths, URLs and paths with variables are left alone.
+fn rebased(directory: &str, path: &str) -> Option<String> {
+    if path.starts_with(['/', '~', '$']) || path.contains("://") || path.contains('@') {
+        return None;
+    }
+    let joined = normalize(&Path::new(directory).join(path));
+    Some(match joined.as_str() {
+        "" => ".".to_owned(),
+        joined if joined.starts_with("..") => joined.to_owned(),
+        joined => format!("./{joined}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        assert_eq!(join(None, "./base.yml"), "base.yml");
+        assert_eq!(join(Some("common/base.yml"), "db.yml"), "common/db.yml");
+        assert_eq!(join(Some("common/base.yml"), "../web.yml"), "web.yml");
+        assert_eq!(
+            join(Some("base.yml"), "../shared/web.yml"),
+            "../shared/web.yml"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Merging of multiple Compose documents
+//!
+//! Implements the [merge rules](https://docs.docker.com/compose/compose-file/13-merge/) of the
+//! Compose spec on untyped YAML values, before the merged document is deserialized:
+//!
+//! - Mappings are merged recursively, values from the later document win
+//! - Sequences are appended, skipping items that are already present
+//! - `command`, `entrypoint` and `healthcheck.test` are replaced rather than appended
+//! - Key/value fields such as `environment` and `labels` are merged by key, whether they are
+//!   written as a mapping or as a list of `KEY=VALUE` strings
+//! - Service `volumes` are unique by their mount target, `secrets` and `configs` by their target
+//! - `!reset` removes a value that was set by an earlier document, `!override` replaces it
+//!   without merging
+
+use serde_yaml::{Mapping, Value};
+
+use crate::span::key_token;
+
+/// How two values at the same path are combined
+enum Rule {
+    /// The later value replaces the earlier one
+    Replace,
+    /// Both values are mappings, or lists of `KEY=VALUE` strings, merged by key
+    KeyValue,
+    /// Both values are mappings, or lists of keys, merged by key
+    Keys,
+    /// Sequence items replace earlier items with the same key
+    UniqueBy(fn(&Value) -> Option<String>),
+    /// Default merge: mappings are merged and sequences appended
+    Merge,
+}
+
+fn rule(path: &[String]) -> Rule {
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    match path.as_slice() {
+        ["services", _, "command" | "entrypoint"] => Rule::Replace,
+        ["services", _, "healthcheck", "test"] => Rule::Replace,
+        ["services", _, "environment" | "labels" | "annotations" | "sysctls"] => Rule::KeyValue,
+        ["services", _, "build", "args" | "labels"] => Rule::KeyValue,
+        ["services", _, "deploy", "labels"] => Rule::KeyValue,
+        ["services", _, "depends_on" | "networks"] => Rule::Keys,
+        ["services", _, "volumes"] => Rule::UniqueBy(volume_target),
+        ["services", _, "secrets" | "configs"] => Rule::UniqueBy(file_target),
+        _ => Rule::Merge,
+    }
+}
+
+/// Merge `overlay` into `base`, following the Compose spec merge rules
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    merge_at(base, overlay, &mut Vec::new());
+}
+
+/// Merge the service `overlay` into the service `base`, as `extends` does
+pub(crate) fn merge_service(base: &mut Value, overlay: Value) {
+    merge_at(
+        base,
+        overlay,
+        &mut vec!["services".to_owned(), String::new()],
+    );
+}
+
+fn merge_at(base: &mut Value, overlay: Value, path: &mut Vec<String>) {
+    let overlay = match overlay {
+        Value::Tagged(tagged) if tagged.tag == "override" => {
+            *base = tagged.value;
+            strip_tags(base);
+            return;
+        }
+        other => other,
+    };
+
+    match (rule(path), base, overlay) {
+        (Rule::Replace, base, overlay) => *base = overlay,
+        (Rule::KeyValue, base, overlay) => {
+            let was_list = base.is_sequence();
+            let mut map = key_values(std::mem::take(base));
+            merge_mapping(&mut map, key_values(overlay), path);
+            // Keep the list syntax, since mappings cannot express variables without a value
+            *base = if was_list {
+                Value::Sequence(
+                    map.into_iter()
+                        .map(|(k, v)| match v {
+                            Value::Null => key_token(&k).into(),
+                            v => format!("{}={}", key_token(&k), key_token(&v)).into(),
+                        })
+                        .collect(),
+                )
+            } else {
+                Value::Mapping(map)
+            };
+        }
+        (Rule::Keys, base, overlay) => {
+            let mut map = keys(std::mem::take(base), path);
+            merge_mapping(&mut map, keys(overlay, path), path);
+            *base = Value::Mapping(map);
+        }
+        (Rule::UniqueBy(key), Value::Sequence(base), Value::Sequence(overlay)) => {
+            for item in overlay {
+                let existing = key(&item).and_then(|k| {
+                    base.iter()
+                        .position(|b| key(b).as_deref() == Some(k.as_str()))
+                });
+                match existing {
+                    Some(i) => base[i] = item,
+                    None => base.push(item),
+                }
+            }
+        }
+        (_, Value::Mapping(base), Value::Mapping(overlay)) => merge_mapping(base, overlay, path),
+        (_, Value::Sequence(base), Value::Sequence(overlay)) => append(base, overlay),
+        // Fields such as `dns` or `env_file` may be a single string or a list of strings
+        (_, base @ Value::String(_), Value::Sequence(overlay)) => {
+            let mut items = vec![std::mem::take(base)];
+            append(&mut items, overlay);
+            *base = Value::Sequence(items);
+        }
+        (_, Value::Sequence(base), overlay @ Value::String(_)) => append(base, vec![overlay]),
+        // The short `build: ./dir` syntax is shorthand for `build: { context: ./dir }`
+        (_, base @ Value::String(_), Value::Mapping(overlay)) if matches!(path.as_slice(), [s, _, b] if s == "services" && b == "build") =>
+        {
+            let mut map = Mapping::new();
+            map.insert("context".into(), std::mem::take(base));
+            merge_mapping(&mut map, overlay, path);
+            *base = Value::Mapping(map);
+        }
+        (_, base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_mapping(base: &mut Mapping, overlay: Mapping, path: &mut Vec<String>) {
+    for (key, value) in overlay {
+        if is_reset(&value) {
+            base.remove(&key);
+            continue;
+        }
+        match base.get_mut(&key) {
+            Some(existing) => {
+                path.push(key_token(&key));
+                merge_at(existing, value, path);
+                path.pop();
+            }
+            None => {
+                let mut value = value;
+                strip_tags(&mut value);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn append(base: &mut Vec<Value>, overlay: Vec<Value>) {
+    for item in overlay {
+        if !base.contains(&item) {
+            base.push(item);
+        }
+    }
+}
+
+fn is_reset(value: &Value) -> bool {
+    matches!(value, Value::Tagged(tagged) if tagged.tag == "reset")
+}
+
+/// Remove `!reset` and `!override` tags that no longer have anything to apply to
+///
+/// `!reset` values are removed from their mapping, `!override` values are replaced with the
+/// value they tag.
+pub(crate) fn strip_tags(value: &mut Value) {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == "override" => {
+            *value = std::mem::take(&mut tagged.value);
+            strip_tags(value);
+        }
+        Value::Tagged(tagged) => strip_tags(&mut tagged.value),
+        Value::Mapping(map) => {
+            map.retain(|_, v| !is_reset(v));
+            map.values_mut().for_each(strip_tags);
+        }
+        Value::Sequence(seq) => {
+            seq.retain(|v| !is_reset(v));
+            seq.iter_mut().for_each(strip_tags);
+        }
+        _ => (),
+    }
+}
+
+/// Convert a list of `KEY=VALUE` strings into a mapping
+fn key_values(value: Value) -> Mapping {
+    match value {
+        Value::Mapping(map) => map,
+        Value::Sequence(seq) => seq
+            .into_iter()
+            .map(|item| match item {
+                Value::String(s) => match s.split_once('=') {
+                    Some((k, v)) => (k.into(), v.into()),
+                    None => (s.into(), Value::Null),
+                },
+                other => (other, Value::Null),
+            })
+            .collect(),
+        _ => Mapping::new(),
+    }
+}
+
+/// Convert a list of keys into a mapping, such as the short `depends_on` and `networks` syntax
+fn keys(value: Value, path: &[String]) -> Mapping {
+    match value {
+        Value::Mapping(map) => map,
+        Value::Sequence(seq) => seq
+            .into_iter()
+            .map(|key| {
+                let value = if path.last().is_some_and(|p| p == "depends_on") {
+                    let mut detail = Mapping::new();
+                    detail.insert("condition".into(), "service_started".into());
+                    Value::Mapping(detail)
+                } else {
+                    Value::Null
+                };
+                (key, value)
+            })
+            .collect(),
+        _ => Mapping::new(),
+    }
+}
+
+/// The container path of a service volume, in either its short or long syntax
+fn volume_target(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => {
+            let parts: Vec<&str> = s.split(':').collect();
+            parts.get(1).or(parts.first()).map(|t| (*t).to_owned())
+        }
+        Value::Mapping(map) => map.get("target")?.as_str().map(str::to_owned),
+        _ => None,
+    }
+}
+
+/// The target of a service secret or config, which defaults to its source
+fn file_target(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Mapping(map) => map
+            .get("target")
+            .or_else(|| map.get("source"))?
+            .as_str()
+            .map(str::to_owned),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compose;
+
+    fn merged(documents: &[&str]) -> Value {
+        let mut values = documents
+            .iter()
+            .map(|d| serde_yaml::from_str::<Value>(d).unwrap());
+        let mut base = values.next().unwrap();
+        values.for_each(|v| merge(&mut base, v));
+        strip_tags(&mut base);
+        base
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        let base = r#"
+        services:
+          web:
+            image: web:1.0
+            command: ["npm", "start"]
+            ports:
+              - "8080:80"
+            environment:
+              - DEBUG=false
+              - LOG_LEVEL=info
+            volumes:
+              - data:/var/lib/data
+              - ./config:/etc/web:ro
+        "#;
+        let overlay = r#"
+        services:
+          web:
+            image: web:2.0
+            command: ["npm", "run", "dev"]
+            ports:
+              - "8080:80"
+              - "9229:9229"
+            environment:
+              DEBUG: "true"
+            volumes:
+              - ./dev-config:/etc/web
+        "#;
+
+        let value = merged(&[base, overlay]);
+        let web = &value["services"]["web"];
+        assert_eq!(web["image"], "web:2.0");
+        assert_eq!(
+            web["command"],
+            serde_yaml::from_str::<Value>("[npm, run, dev]").unwrap()
+        );
+        assert_eq!(web["ports"].as_sequence().unwrap().len(), 2);
+        let environment = web["environment"].as_sequence().unwrap();
+        assert!(environment.contains(&"DEBUG=true".into()));
+        assert!(environment.contains(&"LOG_LEVEL=info".into()));
+        assert_eq!(web["volumes"][0], "data:/var/lib/data");
+        assert_eq!(web["volumes"][1], "./dev-config:/etc/web");
+    }
+
+    #[test]
+    fn test_reset_and_override() {
+        let base = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - "8080:80"
+            labels:
+              team: web
+              tier: frontend
+        "#;
+        let overlay = r#"
+        services:
+          web:
+            ports: !reset []
+            labels: !override
+              tier: backend
+        "#;
+
+        let value = merged(&[base, overlay]);
+        let web = &value["services"]["web"];
+        assert!(web.get("ports").is_none());
+        assert!(web["labels"].get("team").is_none());
+        assert_eq!(web["labels"]["tier"], "backend");
+    }
+
+    #[test]
+    fn test_merged_compose() {
+        let base = r#"
+        services:
+          web:
+            image: web
+            depends_on:
+              - db
+          db:
+            image: postgres
+        "#;
+        let overlay = r#"
+        services:
+          web:
+            depends_on:
+              cache:
+                condition: service_healthy
+          cache:
+            image: redis
+        "#;
+        let prod = r#"
+        services:
+          web:
+            depends_on:
+              - queue
+        "#;
+
+        let errors = Compose::builder()
+            .document(base)
+            .document(overlay)
+            .document(prod)
+            .build()
+            .unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.path, "/services/web/depends_on/queue");
+        assert_eq!(error.span.unwrap().document, 2);
+
+        let compose = Compose::builder()
+            .document(base)
+            .document(overlay)
+            .build()
+            .unwrap();
+        assert_eq!(compose.services.len(), 3);
+    }
+}
@@ -0,0 +1,197 @@
+//! YAML merge keys
+//!
+//! Resolves [merge keys](https://yaml.org/type/merge.html) such as `<<: *defaults`, which
+//! `serde_yaml` leaves in the document as a plain `<<` key. Aliases themselves are already
+//! expanded by the parser.
+//!
+//! Keys set explicitly in a mapping take precedence over merged keys, and when merging a list
+//! of mappings, earlier mappings take precedence over later ones. Merged mappings may contain
+//! merge keys of their own.
+
+use serde_yaml::Value;
+
+use crate::{
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    span::{key_token, pointer},
+};
+
+/// Resolve every merge key within `value`, which is found at `path`
+pub(crate) fn apply(value: &mut Value, path: &str, errors: &mut ValidationErrors) {
+    match value {
+        Value::Mapping(map) => {
+            // Resolve nested merge keys first, including those of the merged mappings
+            for (key, value) in map.iter_mut() {
+                apply(value, &pointer(path, key_token(key)), errors);
+            }
+
+            let Some(merge) = map.remove("<<") else {
+                return;
+            };
+            let sources = match merge {
+                Value::Mapping(source) => vec![source],
+                Value::Sequence(sources) => {
+                    let mut mappings = Vec::new();
+                    for (index, source) in sources.into_iter().enumerate() {
+                        match source {
+                            Value::Mapping(source) => mappings.push(source),
+                            _ => errors.add_error(ValidationError::new(
+                                ErrorCode::InvalidMergeKey,
+                                &pointer(&pointer(path, "<<"), index),
+                                "Merge key lists may only contain mappings",
+                            )),
+                        }
+                    }
+                    mappings
+                }
+                _ => {
+                    errors.add_error(ValidationError::new(
+                        ErrorCode::InvalidMergeKey,
+                        &pointer(path, "<<"),
+                        "Merge key must be a mapping or a list of mappings",
+                    ));
+                    return;
+                }
+            };
+
+            for source in sources {
+                for (key, value) in source {
+                    map.entry(key).or_insert(value);
+                }
+            }
+        }
+        Value::Sequence(seq) => {
+            for (index, value) in seq.iter_mut().enumerate() {
+                apply(value, &pointer(path, index), errors);
+            }
+        }
+        Value::Tagged(tagged) => apply(&mut tagged.value, path, errors),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compose;
+
+    /// Typical merge key patterns, along with the `web` service they should resolve to
+    const CORPUS: [(&str, &str, &str); 6] = [
+        (
+            "shared defaults with an override",
+            r#"
+            x-common: &common
+              image: web:1.0
+              restart: always
+            services:
+              web:
+                <<: *common
+                image: web:2.0
+            "#,
+            "{image: web:2.0, restart: always}",
+        ),
+        (
+            "list of merges, earlier mappings win",
+            r#"
+            x-logging: &logging
+              restart: "no"
+              hostname: logged
+            x-base: &base
+              restart: always
+              image: base
+            services:
+              web:
+                <<: [*logging, *base]
+                hostname: web
+            "#,
+            "{hostname: web, restart: 'no', image: base}",
+        ),
+        (
+            "chained merges",
+            r#"
+            x-base: &base
+              image: base
+              init: true
+            x-app: &app
+              <<: *base
+              image: app
+            services:
+              web:
+                <<: *app
+                hostname: web
+            "#,
+            "{hostname: web, image: app, init: true}",
+        ),
+        (
+            "merge within a field",
+            r#"
+            x-env: &env
+              LOG_LEVEL: info
+              TZ: UTC
+            services:
+              web:
+                image: web
+                environment:
+                  <<: *env
+                  LOG_LEVEL: debug
+            "#,
+            "{image: web, environment: {LOG_LEVEL: debug, TZ: UTC}}",
+        ),
+        (
+            "plain aliases",
+            r#"
+            x-env: &env
+              TZ: UTC
+            services:
+              web:
+                image: web
+                environment: *env
+            "#,
+            "{image: web, environment: {TZ: UTC}}",
+        ),
+        (
+            "anchored service",
+            r#"
+            services:
+              api: &api
+                image: api
+                restart: always
+              web:
+                <<: *api
+                image: web
+            "#,
+            "{image: web, restart: always}",
+        ),
+    ];
+
+    #[test]
+    fn test_merge_key_corpus() {
+        for (name, yaml, expected) in CORPUS {
+            let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+            let mut errors = ValidationErrors::new();
+            apply(&mut value, "", &mut errors);
+            assert!(!errors.has_errors(), "{name}: {errors}");
+
+            let expected: Value = serde_yaml::from_str(expected).unwrap();
+            assert_eq!(value["services"]["web"], expected, "{name}");
+            assert!(Compose::new(yaml).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_merge_key() {
+        let yaml = r#"
+        x-ports: &ports
+          - "80:80"
+        services:
+          web:
+            <<: *ports
+            image: web
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::InvalidMergeKey);
+        assert_eq!(error.path, "/services/web/<</0");
+        assert_eq!(error.span.unwrap().start.line, 6);
+    }
+}
@@ -1,18 +1,22 @@
 //! Network fields and validation
 
 use crate::compose::Compose;
+use indexmap::IndexMap;
 use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 use crate::{
     compose::Validate,
-    errors::{ValidationError, ValidationErrors},
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    extensions::Extensions,
     services::Labels,
+    span::pointer,
 };
 
 /// Represents the top level [Network](https://docs.docker.com/compose/compose-file/06-networks/) element
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Network {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attachable: Option<bool>,
@@ -24,7 +28,7 @@ pub struct Network {
     pub driver: Option<Driver>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub driver_opts: Option<HashMap<String, String>>,
+    pub driver_opts: Option<IndexMap<String, String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub enable_ipv6: Option<bool>,
@@ -45,10 +49,16 @@ pub struct Network {
     pub name: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<HashMap<String, String>>,
+    pub options: Option<IndexMap<String, String>>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Ipam {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub driver: Option<Driver>,
@@ -57,10 +67,11 @@ pub struct Ipam {
     pub config: Option<Config>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<HashMap<String, String>>,
+    pub options: Option<IndexMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum Driver {
     None,
@@ -70,6 +81,13 @@ pub enum Driver {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "IpamConfig"),
+    ts(rename = "IpamConfig")
+)]
 pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub subnet: Option<String>,
@@ -81,39 +99,35 @@ pub struct Config {
     pub gateway: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub aux_addresses: Option<HashMap<String, String>>,
+    pub aux_addresses: Option<IndexMap<String, String>>,
 }
 
 impl Validate for Network {
-    fn validate(&self, _: &Compose, errors: &mut ValidationErrors) {
-        if let Some(config) = &self.config {
-            for c in config {
-                if let Some(subnet) = &c.subnet {
-                    match subnet.parse::<IpNetwork>() {
-                        Err(e) => errors.add_error(ValidationError::InvalidValue(format!(
-                            "Invalid subnet address: {}",
-                            e
-                        ))),
-                        _ => (),
-                    }
-                }
-                if let Some(ip_range) = &c.ip_range {
-                    match ip_range.parse::<IpNetwork>() {
-                        Err(e) => errors.add_error(ValidationError::InvalidValue(format!(
-                            "Invalid ip_range address: {}",
-                            e
-                        ))),
-                        _ => (),
-                    }
-                }
-                if let Some(gateway) = &c.gateway {
-                    match gateway.parse::<IpNetwork>() {
-                        Err(e) => errors.add_error(ValidationError::InvalidValue(format!(
-                            "Invalid gateway address: {}",
-                            e
-                        ))),
-                        _ => (),
-                    }
+    fn validate(&self, _: &Compose, path: &str, errors: &mut ValidationErrors) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let path = pointer(path, "config");
+        for (i, c) in config.iter().enumerate() {
+            let path = pointer(&path, i);
+            let addresses = [
+                ("subnet", &c.subnet),
+                ("ip_range", &c.ip_range),
+                ("gateway", &c.gateway),
+            ];
+            for (field, address) in addresses {
+                let Some(address) = address else {
+                    continue;
+                };
+                if let Err(e) = address.parse::<IpNetwork>() {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::InvalidAddress,
+                            &pointer(&path, field),
+                            format!("Invalid {field} address: {e}"),
+                        )
+                        .with_value(address),
+                    )
                 }
             }
         }
@@ -185,6 +199,8 @@ mod tests {
 
         let compose = Compose::new(yaml);
         assert!(compose.is_err());
+        // The schema also reports the misplaced `config` and `options` keys
+        #[cfg(not(feature = "compose-spec"))]
         assert!(compose.is_err_and(|e| e.all_errors().len() == 1))
     }
 }
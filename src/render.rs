@@ -0,0 +1,357 @@
+//! Topology diagrams
+//!
+//! Renders the services of a manifest along with the networks, named volumes, secrets and
+//! configs they use, and the edges of its [`DependencyGraph`], as Graphviz DOT or Mermaid
+//! flowcharts. Resources that services reference without defining them, such as the implicit
+//! `default` network, are included.
+
+use std::fmt::Write;
+
+use crate::{
+    compose::Normalize,
+    graph::{DependencyGraph, Edge},
+    services::{
+        networks::Networks,
+        secrets::Secret,
+        volumes::{LongVolumeOptions, VolumeType, Volumes},
+        Config, Service,
+    },
+    Compose,
+};
+
+/// A kind of resource that services use
+#[derive(Clone, Copy, PartialEq)]
+enum Resource {
+    Network,
+    Volume,
+    Secret,
+    Config,
+}
+
+impl Resource {
+    const ALL: [Resource; 4] = [
+        Resource::Network,
+        Resource::Volume,
+        Resource::Secret,
+        Resource::Config,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Resource::Network => "network",
+            Resource::Volume => "volume",
+            Resource::Secret => "secret",
+            Resource::Config => "config",
+        }
+    }
+}
+
+/// A service using a resource
+struct Attachment {
+    service: usize,
+    resource: Resource,
+    name: usize,
+    /// Where a volume is mounted
+    label: Option<String>,
+}
+
+/// Everything a diagram shows, with resources referenced by their index
+struct Topology<'a> {
+    services: Vec<&'a str>,
+    resources: Vec<(Resource, Vec<String>)>,
+    attachments: Vec<Attachment>,
+    graph: DependencyGraph,
+}
+
+impl<'a> Topology<'a> {
+    fn new(compose: &'a Compose) -> Self {
+        let defined = |names: Option<Vec<&String>>| -> Vec<String> {
+            names.into_iter().flatten().cloned().collect()
+        };
+        let mut topology = Topology {
+            services: compose.services.keys().map(String::as_str).collect(),
+            resources: vec![
+                (
+                    Resource::Network,
+                    defined(compose.networks.as_ref().map(|n| n.keys().collect())),
+                ),
+                (
+                    Resource::Volume,
+                    defined(compose.volumes.as_ref().map(|v| v.keys().collect())),
+                ),
+                (
+                    Resource::Secret,
+                    defined(compose.secrets.as_ref().map(|s| s.keys().collect())),
+                ),
+                (
+                    Resource::Config,
+                    defined(compose.configs.as_ref().map(|c| c.keys().collect())),
+                ),
+            ],
+            attachments: Vec::new(),
+            graph: compose.dependency_graph(),
+        };
+        for (i, service) in compose.services.values().enumerate() {
+            for (resource, name, label) in attachments(service) {
+                let name = topology.resource(resource, name);
+                topology.attachments.push(Attachment {
+                    service: i,
+                    resource,
+                    name,
+                    label,
+                });
+            }
+        }
+        topology
+    }
+
+    /// The index of the resource `name`, adding it if it is not defined
+    fn resource(&mut self, resource: Resource, name: String) -> usize {
+        let (_, names) = self
+            .resources
+            .iter_mut()
+            .find(|(r, _)| *r == resource)
+            .expect("every kind of resource is listed");
+        match names.iter().position(|n| *n == name) {
+            Some(i) => i,
+            None => {
+                names.push(name);
+                names.len() - 1
+            }
+        }
+    }
+
+    fn names(&self, resource: Resource) -> &[String] {
+        self.resources
+            .iter()
+            .find(|(r, _)| *r == resource)
+            .map(|(_, names)| names.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn service(&self, name: &str) -> usize {
+        self.services.iter().position(|s| *s == name).unwrap_or(0)
+    }
+}
+
+/// The resources `service` uses, with a label for the edge
+fn attachments(service: &Service) -> Vec<(Resource, String, Option<String>)> {
+    let mut attachments = Vec::new();
+
+    match &service.networks {
+        Some(Networks::List(names)) => {
+            attachments.extend(names.iter().map(|n| (Resource::Network, n.clone(), None)))
+        }
+        Some(Networks::Map(names)) => {
+            attachments.extend(names.keys().map(|n| (Resource::Network, n.clone(), None)))
+        }
+        // Services without networks join the default network, unless they share another
+        // network stack
+        None if service.network_mode.is_none() => {
+            attachments.push((Resource::Network, "default".to_owned(), None))
+        }
+        None => (),
+    }
+
+    for volume in service.volumes.iter().flatten() {
+        let mut volume = volume.clone();
+        volume.normalize();
+        if let Volumes::Long(LongVolumeOptions {
+            volume_type: VolumeType::Volume,
+            source: Some(source),
+            target,
+            ..
+        }) = volume
+        {
+            attachments.push((Resource::Volume, source, Some(target)));
+        }
+    }
+
+    for secret in service.secrets.iter().flatten() {
+        let source = match secret {
+            Secret::Short(source) => source,
+            Secret::Long(long) => &long.source,
+        };
+        attachments.push((Resource::Secret, source.clone(), None));
+    }
+
+    for config in service.configs.iter().flatten() {
+        let source = match config {
+            Config::Short(source) => source,
+            Config::Long(long) => &long.source,
+        };
+        attachments.push((Resource::Config, source.clone(), None));
+    }
+
+    attachments
+}
+
+/// The label of a dependency edge, its `depends_on` condition or the field it comes from
+fn edge_label(edge: &Edge) -> String {
+    let label = match edge.condition {
+        Some(condition) => condition.to_string(),
+        None => edge.kind.to_string(),
+    };
+    if edge.required {
+        label
+    } else {
+        format!("{label}, optional")
+    }
+}
+
+/// Render `compose` as a Graphviz DOT digraph
+pub(crate) fn dot(compose: &Compose) -> String {
+    let topology = Topology::new(compose);
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let service_id = |i: usize| quote(&format!("service:{}", topology.services[i]));
+    let resource_id =
+        |resource: Resource, name: &str| quote(&format!("{}:{name}", resource.name()));
+
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph compose {{");
+    let _ = writeln!(out, "  rankdir=LR;");
+    for (i, name) in topology.services.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "  {} [label={}, shape=box];",
+            service_id(i),
+            quote(name)
+        );
+    }
+    for (resource, names) in &topology.resources {
+        let shape = match resource {
+            Resource::Network => "ellipse",
+            Resource::Volume => "cylinder",
+            Resource::Secret => "octagon",
+            Resource::Config => "note",
+        };
+        for name in names {
+            let _ = writeln!(
+                out,
+                "  {} [label={}, shape={shape}];",
+                resource_id(*resource, name),
+                quote(name)
+            );
+        }
+    }
+
+    for attachment in &topology.attachments {
+        let names = topology.names(attachment.resource);
+        let resource = resource_id(attachment.resource, &names[attachment.name]);
+        let service = service_id(attachment.service);
+        let _ = match (attachment.resource, &attachment.label) {
+            (Resource::Secret | Resource::Config, _) => {
+                writeln!(out, "  {resource} -> {service} [style=dashed];")
+            }
+            (_, Some(label)) => writeln!(
+                out,
+                "  {service} -> {resource} [arrowhead=none, label={}];",
+                quote(label)
+            ),
+            (_, None) => writeln!(
+                out,
+                "  {service} -> {resource} [arrowhead=none, style=dotted];"
+            ),
+        };
+    }
+
+    for edge in topology.graph.edges() {
+        let style = if edge.required { "" } else { ", style=dashed" };
+        let _ = writeln!(
+            out,
+            "  {} -> {} [label={}{style}];",
+            service_id(topology.service(&edge.from)),
+            service_id(topology.service(&edge.to)),
+            quote(&edge_label(edge))
+        );
+    }
+    let _ = writeln!(out, "}}");
+    out
+}
+
+/// Render `compose` as a Mermaid flowchart
+pub(crate) fn mermaid(compose: &Compose) -> String {
+    let topology = Topology::new(compose);
+    let text = |s: &str| format!("\"{}\"", s.replace('"', "#quot;"));
+    let prefix = |resource: Resource| match resource {
+        Resource::Network => "n",
+        Resource::Volume => "v",
+        Resource::Secret => "x",
+        Resource::Config => "c",
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "flowchart LR");
+    for (i, name) in topology.services.iter().enumerate() {
+        let _ = writeln!(out, "  s{i}[{}]", text(name));
+    }
+    for resource in Resource::ALL {
+        for (i, name) in topology.names(resource).iter().enumerate() {
+            let name = text(name);
+            let id = prefix(resource);
+            let _ = match resource {
+                Resource::Network => writeln!(out, "  {id}{i}([{name}])"),
+                Resource::Volume => writeln!(out, "  {id}{i}[({name})]"),
+                Resource::Secret => writeln!(out, "  {id}{i}{{{{{name}}}}}"),
+                Resource::Config => writeln!(out, "  {id}{i}>{name}]"),
+            };
+        }
+    }
+
+    for attachment in &topology.attachments {
+        let resource = format!("{}{}", prefix(attachment.resource), attachment.name);
+        let service = format!("s{}", attachment.service);
+        let _ = match (attachment.resource, &attachment.label) {
+            (Resource::Secret | Resource::Config, _) => {
+                writeln!(out, "  {resource} -.-> {service}")
+            }
+            (_, Some(label)) => writeln!(out, "  {service} ---|{}| {resource}", text(label)),
+            (_, None) => writeln!(out, "  {service} -.- {resource}"),
+        };
+    }
+
+    for edge in topology.graph.edges() {
+        let arrow = if edge.required { "-->" } else { "-.->" };
+        let _ = writeln!(
+            out,
+            "  s{} {arrow}|{}| s{}",
+            topology.service(&edge.from),
+            text(&edge_label(edge)),
+            topology.service(&edge.to)
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::check;
+
+    #[test]
+    fn test_golden_files() {
+        let yaml = include_str!("../testdata/topology.yaml");
+        let compose = Compose::new(yaml).unwrap();
+        check("testdata/topology.dot", &compose.to_dot());
+        check("testdata/topology.mmd", &compose.to_mermaid());
+    }
+
+    #[test]
+    fn test_escaping() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            volumes:
+              - 'data:/srv/"quoted"'
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        assert!(compose.to_dot().contains(
+            r#""service:web" -> "volume:data" [arrowhead=none, label="/srv/\"quoted\""];"#
+        ));
+        assert!(compose
+            .to_mermaid()
+            .contains(r#"s0 ---|"/srv/#quot;quoted#quot;"| v0"#));
+    }
+}
@@ -0,0 +1,328 @@
+//! Lint rules
+//!
+//! Checks beyond the structure of a manifest, such as deprecated fields, are [`Rule`]s. A
+//! [`Validator`] holds the built-in rules along with any registered by the caller, and can
+//! enable, disable or change the severity of each one by its id.
+//!
+//! ```
+//! use compose_validatr::{
+//!     errors::Severity,
+//!     rules::{Diagnostics, Rule, Validator},
+//!     span::pointer,
+//!     Compose,
+//! };
+//!
+//! /// Every service must pin its image to a registry we control
+//! struct InternalRegistry;
+//!
+//! impl Rule for InternalRegistry {
+//!     fn id(&self) -> &'static str {
+//!         "internal-registry"
+//!     }
+//!
+//!     fn description(&self) -> &'static str {
+//!         "Images must come from registry.example.com"
+//!     }
+//!
+//!     fn default_severity(&self) -> Severity {
+//!         Severity::Error
+//!     }
+//!
+//!     fn check(&self, compose: &Compose, diagnostics: &mut Diagnostics) {
+//!         for (name, service) in &compose.services {
+//!             let Some(image) = &service.image else { continue };
+//!             if !image.starts_with("registry.example.com/") {
+//!                 let path = pointer(&pointer("/services", name), "image");
+//!                 diagnostics.report_value(&path, image, "Image is not from the internal registry");
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! let validator = Validator::new().rule(InternalRegistry).disable("deprecated");
+//! let yaml = "version: '3'\nservices:\n  web:\n    image: nginx\n";
+//!
+//! let errors = Compose::builder().document(yaml).validator(&validator).build().unwrap_err();
+//! assert_eq!(errors.all_errors()[0].path, "/services/web/image");
+//! assert_eq!(errors.all_errors()[0].code.as_str(), "internal-registry");
+//! ```
+
+mod credentials;
+mod deprecated;
+pub mod security;
+
+pub use credentials::HardcodedCredentials;
+pub use deprecated::Deprecated;
+
+use crate::{
+    errors::{ErrorCode, Severity, ValidationError, ValidationErrors},
+    Compose,
+};
+
+/// A check over a whole [`Compose`] manifest
+pub trait Rule {
+    /// Unique id, such as `deprecated`, used to configure the rule in a [`Validator`]
+    fn id(&self) -> &'static str;
+
+    /// What the rule checks, in a sentence
+    fn description(&self) -> &'static str;
+
+    /// The severity of the problems the rule reports, unless the [`Validator`] overrides it
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Whether the rule runs without being enabled in the [`Validator`]
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    /// The code of the problems the rule reports, [`ErrorCode::Rule`] with the rule id by default
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Rule(self.id())
+    }
+
+    /// Report the problems found in `compose`
+    fn check(&self, compose: &Compose, diagnostics: &mut Diagnostics);
+}
+
+/// Collects the problems reported by a [`Rule`], with the rule's code and configured severity
+#[derive(Debug)]
+pub struct Diagnostics {
+    code: ErrorCode,
+    severity: Severity,
+    reported: Vec<ValidationError>,
+}
+
+impl Diagnostics {
+    /// Report a problem with the node at `path`, a JSON pointer such as `/services/web/image`
+    pub fn report(&mut self, path: &str, message: impl Into<String>) {
+        self.reported
+            .push(ValidationError::new(self.code, path, message).with_severity(self.severity));
+    }
+
+    /// Report a problem with the node at `path`, including its offending value
+    pub fn report_value(
+        &mut self,
+        path: &str,
+        value: impl Into<String>,
+        message: impl Into<String>,
+    ) {
+        self.reported.push(
+            ValidationError::new(self.code, path, message)
+                .with_value(value)
+                .with_severity(self.severity),
+        );
+    }
+}
+
+/// The built-in rules
+pub fn builtin() -> Vec<Box<dyn Rule>> {
+    let mut rules: Vec<Box<dyn Rule>> = vec![Box::new(Deprecated), Box::new(HardcodedCredentials)];
+    rules.extend(security::rules());
+    rules
+}
+
+/// A configured set of [`Rule`]s
+///
+/// Use it with [`ComposeBuilder::validator`] to check manifests as they are built, or run it
+/// over an existing manifest with [`Validator::check`]. Manifests built without a validator
+/// are checked with [`Validator::default`].
+///
+/// [`ComposeBuilder::validator`]: crate::compose::ComposeBuilder::validator
+pub struct Validator {
+    rules: Vec<Registered>,
+}
+
+struct Registered {
+    rule: Box<dyn Rule>,
+    enabled: bool,
+    severity: Severity,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Validator {
+    /// A validator with the [`builtin`] rules, each enabled according to
+    /// [`Rule::enabled_by_default`]
+    pub fn new() -> Self {
+        builtin()
+            .into_iter()
+            .fold(Validator { rules: Vec::new() }, |validator, rule| {
+                validator.boxed_rule(rule)
+            })
+    }
+
+    /// Register `rule`, replacing any rule with the same id
+    pub fn rule(self, rule: impl Rule + 'static) -> Self {
+        self.boxed_rule(Box::new(rule))
+    }
+
+    fn boxed_rule(mut self, rule: Box<dyn Rule>) -> Self {
+        let registered = Registered {
+            enabled: rule.enabled_by_default(),
+            severity: rule.default_severity(),
+            rule,
+        };
+        match self.position(registered.rule.id()) {
+            Some(i) => self.rules[i] = registered,
+            None => self.rules.push(registered),
+        }
+        self
+    }
+
+    /// Run the rule `id`. Ids that are not registered are ignored.
+    pub fn enable(self, id: &str) -> Self {
+        self.configure(id, |rule| rule.enabled = true)
+    }
+
+    /// Do not run the rule `id`. Ids that are not registered are ignored.
+    pub fn disable(self, id: &str) -> Self {
+        self.configure(id, |rule| rule.enabled = false)
+    }
+
+    /// Report the problems found by the rule `id` with `severity`, such as
+    /// [`Severity::Error`] to fail the build. Ids that are not registered are ignored.
+    pub fn severity(self, id: &str, severity: Severity) -> Self {
+        self.configure(id, |rule| rule.severity = severity)
+    }
+
+    fn configure(mut self, id: &str, f: impl FnOnce(&mut Registered)) -> Self {
+        if let Some(i) = self.position(id) {
+            f(&mut self.rules[i]);
+        }
+        self
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.rules.iter().position(|r| r.rule.id() == id)
+    }
+
+    /// Every registered rule, enabled or not
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|r| r.rule.as_ref())
+    }
+
+    /// Whether the rule `id` is registered and enabled
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.position(id).is_some_and(|i| self.rules[i].enabled)
+    }
+
+    /// Run the enabled rules over `compose`
+    ///
+    /// The problems have no spans, as `compose` no longer knows its source. Use
+    /// [`ComposeBuilder::validator`] to have them located.
+    ///
+    /// [`ComposeBuilder::validator`]: crate::compose::ComposeBuilder::validator
+    pub fn check(&self, compose: &Compose) -> ValidationErrors {
+        let mut errors = ValidationErrors::new();
+        for registered in self.rules.iter().filter(|r| r.enabled) {
+            let mut diagnostics = Diagnostics {
+                code: registered.rule.code(),
+                severity: registered.severity,
+                reported: Vec::new(),
+            };
+            registered.rule.check(compose, &mut diagnostics);
+            errors.extend(diagnostics.reported);
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::pointer;
+
+    struct Latest;
+
+    impl Rule for Latest {
+        fn id(&self) -> &'static str {
+            "latest-tag"
+        }
+
+        fn description(&self) -> &'static str {
+            "Images should not use the latest tag"
+        }
+
+        fn enabled_by_default(&self) -> bool {
+            false
+        }
+
+        fn check(&self, compose: &Compose, diagnostics: &mut Diagnostics) {
+            for (name, service) in &compose.services {
+                if let Some(image) = service.image.as_ref().filter(|i| i.ends_with(":latest")) {
+                    let path = pointer(&pointer("/services", name), "image");
+                    diagnostics.report_value(&path, image, "Image uses the latest tag");
+                }
+            }
+        }
+    }
+
+    const YAML: &str = r#"
+    version: "3.9"
+    services:
+      gitlab:
+        image: gitlab/gitlab-ce:latest
+    "#;
+
+    #[test]
+    fn test_default_rules() {
+        let validator = Validator::new();
+        assert!(validator.is_enabled("deprecated"));
+        let compose = Compose::new(YAML).unwrap();
+        assert_eq!(compose.warnings.len(), 1);
+        assert_eq!(compose.warnings[0].code, ErrorCode::Deprecated);
+
+        let validator = validator.disable("deprecated");
+        let compose = Compose::builder()
+            .document(YAML)
+            .validator(&validator)
+            .build()
+            .unwrap();
+        assert!(compose.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let validator = Validator::new().rule(Latest);
+        assert!(!validator.is_enabled("latest-tag"));
+        let compose = Compose::new(YAML).unwrap();
+        assert!(validator
+            .check(&compose)
+            .with_code(ErrorCode::Rule("latest-tag"))
+            .next()
+            .is_none());
+
+        let validator = validator.enable("latest-tag");
+        let errors = validator.check(&compose);
+        let error = errors
+            .with_code(ErrorCode::Rule("latest-tag"))
+            .next()
+            .unwrap();
+        assert_eq!(error.severity, Severity::Warning);
+        assert_eq!(error.path, "/services/gitlab/image");
+        assert_eq!(error.value.as_deref(), Some("gitlab/gitlab-ce:latest"));
+
+        let validator = validator.severity("latest-tag", Severity::Error);
+        let errors = Compose::builder()
+            .document(YAML)
+            .validator(&validator)
+            .build()
+            .unwrap_err();
+        let error = errors
+            .with_code(ErrorCode::Rule("latest-tag"))
+            .next()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 5, column 16: [latest-tag] /services/gitlab/image: Image uses the latest tag"
+        );
+        // Warnings are reported along with the errors
+        assert_eq!(errors.with_severity(Severity::Warning).count(), 1);
+    }
+}
@@ -0,0 +1,58 @@
+//! Deprecated and obsolete fields
+
+use crate::{errors::ErrorCode, span::pointer, Compose};
+
+use super::{Diagnostics, Rule};
+
+/// Reports fields that are deprecated in favor of `deploy`, and the obsolete top level
+/// `version`
+pub struct Deprecated;
+
+impl Rule for Deprecated {
+    fn id(&self) -> &'static str {
+        "deprecated"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fields that are deprecated or obsolete in the Compose specification"
+    }
+
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Deprecated
+    }
+
+    fn check(&self, compose: &Compose, diagnostics: &mut Diagnostics) {
+        for (name, service) in &compose.services {
+            let path = pointer("/services", name);
+            let deprecated = [
+                (
+                    "cpus",
+                    service.cpus.is_some(),
+                    "deploy.resources.limits.cpus",
+                ),
+                (
+                    "mem_limit",
+                    service.mem_limit.is_some(),
+                    "deploy.resources.limits.memory",
+                ),
+                (
+                    "mem_reservation",
+                    service.mem_reservation.is_some(),
+                    "deploy.resources.reservations.memory",
+                ),
+                ("scale", service.scale.is_some(), "deploy.replicas"),
+            ];
+            for (field, set, replacement) in deprecated {
+                if set {
+                    diagnostics.report(
+                        &pointer(&path, field),
+                        format!("{field} is deprecated, use {replacement} instead"),
+                    );
+                }
+            }
+        }
+        if let Some(version) = &compose.version {
+            diagnostics.report_value("/version", version, "version is obsolete and ignored");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Security rules
+//!
+//! Risky service settings, such as `privileged: true` or mounting the Docker socket. None of
+//! these rules run unless enabled in the [`Validator`](super::Validator), as some services
+//! legitimately need them.
+
+use crate::{
+    services::{Capabilities, Service},
+    span::pointer,
+    Compose,
+};
+
+use super::{Diagnostics, Rule};
+
+/// Host paths that give a container control over the host when bind mounted
+const SENSITIVE_PATHS: [&str; 2] = ["/", "/etc"];
+
+/// Paths of the Docker daemon socket
+const DOCKER_SOCKETS: [&str; 2] = ["/var/run/docker.sock", "/run/docker.sock"];
+
+/// The security rules
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(Privileged),
+        Box::new(CapAddAll),
+        Box::new(HostNetwork),
+        Box::new(HostPid),
+        Box::new(DockerSocket),
+        Box::new(SensitiveMount),
+        Box::new(SeccompUnconfined),
+        Box::new(ReadOnlyRoot),
+        Box::new(RootUser),
+    ]
+}
+
+/// Define an opt-in rule that checks each service on its own
+macro_rules! service_rule {
+    (
+        $(#[$doc:meta])*
+        $rule:ident, $id:literal, $description:literal,
+        |$service:ident, $path:ident, $diagnostics:ident| $check:block
+    ) => {
+        $(#[$doc])*
+        pub struct $rule;
+
+        impl Rule for $rule {
+            fn id(&self) -> &'static str {
+                $id
+            }
+
+            fn description(&self) -> &'static str {
+                $description
+            }
+
+            fn enabled_by_default(&self) -> bool {
+                false
+            }
+
+            fn check(&self, compose: &Compose, diagnostics: &mut Diagnostics) {
+                let check = |$service: &Service, $path: &str, $diagnostics: &mut Diagnostics| {
+                    $check
+                };
+                for (name, service) in &compose.services {
+                    check(service, &pointer("/services", name), diagnostics);
+                }
+            }
+        }
+    };
+}
+
+service_rule!(
+    /// Reports `privileged: true`
+    Privileged,
+    "privileged",
+    "Services should not run privileged",
+    |service, path, diagnostics| {
+        if service.privileged == Some(true) {
+            diagnostics.report(
+                &pointer(path, "privileged"),
+                "Service runs privileged, with full access to the host devices and kernel",
+            );
+        }
+    }
+);
+
+service_rule!(
+    /// Reports `ALL` in `cap_add`
+    CapAddAll,
+    "cap-add-all",
+    "Services should add only the capabilities they need",
+    |service, path, diagnostics| {
+        let capabilities = service.cap_add.iter().flatten().enumerate();
+        for (i, _) in capabilities.filter(|(_, c)| matches!(c, Capabilities::All)) {
+            diagnostics.report_value(
+                &pointer(&pointer(path, "cap_add"), i),
+                "ALL",
+                "Service adds all capabilities, add only the ones it needs instead",
+            );
+        }
+    }
+);
+
+service_rule!(
+    /// Reports `network_mode: host`
+    HostNetwork,
+    "host-network",
+    "Services should not share the network stack of the host",
+    |service, path, diagnostics| {
+        if service.network_mode.as_deref() == Some("host") {
+            diagnostics.report_value(
+                &pointer(path, "network_mode"),
+                "host",
+                "Service shares the network stack of the host",
+            );
+        }
+    }
+);
+
+service_rule!(
+    /// Reports `pid: host`
+    HostPid,
+    "host-pid",
+    "Services should not share the process namespace of the host",
+    |service, path, diagnostics| {
+        if service.pid.as_deref() == Some("host") {
+            diagnostics.report_value(
+                &pointer(path, "pid"),
+                "host",
+                "Service shares the process namespace of the host and can see its processes",
+            );
+        }
+    }
+);
+
+service_rule!(
+    /// Reports volumes that mount the Docker socket
+    DockerSocket,
+    "docker-socket",
+    "Services should not mount the Docker socket",
+    |service, path, diagnostics| {
+        for (i, volume) in service.volumes.iter().flatten().enumerate() {
+            let Some(source) = volume.bind_source() else {
+                continue;
+            };
+            if DOCKER_SOCKETS.contains(&source.as_str()) {
+                diagnostics.report_value(
+                    &pointer(&pointer(path, "volumes"), i),
+                    source,
+                    "Service mounts the Docker socket, which grants root access to the host",
+                );
+            }
+        }
+    }
+);
+
+service_rule!(
+    /// Reports bind mounts of `/` or `/etc`
+    SensitiveMount,
+    "sensitive-mount",
+    "Services should not bind mount the root or configuration directories of the host",
+    |service, path, diagnostics| {
+        for (i, volume) in service.volumes.iter().flatten().enumerate() {
+            let Some(source) = volume.bind_source() else {
+                continue;
+            };
+            let trimmed = match source.trim_end_matches('/') {
+                "" => "/",
+                trimmed => trimmed,
+            };
+            if SENSITIVE_PATHS.contains(&trimmed) {
+                diagnostics.report_value(
+                    &pointer(&pointer(path, "volumes"), i),
+                    &source,
+                    format!("Service bind mounts {trimmed} from the host"),
+                );
+            }
+        }
+    }
+);
+
+service_rule!(
+    /// Reports `seccomp:unconfined` in `security_opt`
+    SeccompUnconfined,
+    "seccomp-unconfined",
+    "Services should keep the default seccomp profile",
+    |service, path, diagnostics| {
+        for (i, option) in service.security_opt.iter().flatten().enumerate() {
+            if matches!(option.as_str(), "seccomp:unconfined" | "seccomp=unconfined") {
+                diagnostics.report_value(
+                    &pointer(&pointer(path, "security_opt"), i),
+                    option,
+                    "Service disables seccomp, allowing every system call",
+                );
+            }
+        }
+    }
+);
+
+service_rule!(
+    /// Reports services without `read_only: true`
+    ReadOnlyRoot,
+    "read-only-root",
+    "Services should run with a read only root filesystem",
+    |service, path, diagnostics| {
+        if service.read_only != Some(true) {
+            diagnostics.report(
+                &pointer(path, "read_only"),
+                "Service root filesystem is writable, set read_only: true",
+            );
+        }
+    }
+);
+
+service_rule!(
+    /// Reports services without `user`, or running as `root`
+    RootUser,
+    "root-user",
+    "Services should run as an unprivileged user",
+    |service, path, diagnostics| {
+        let user = service.user.as_deref();
+        let name = user.map(|user| user.split(':').next().unwrap_or_default());
+        match name {
+            None => diagnostics.report(
+                &pointer(path, "user"),
+                "Service runs as the user of the image, which is often root, set user",
+            ),
+            Some("root" | "0") => diagnostics.report_value(
+                &pointer(path, "user"),
+                user.unwrap_or_default(),
+                "Service runs as root, set user to an unprivileged user",
+            ),
+            Some(_) => (),
+        }
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::ErrorCode, rules::Validator};
+
+    #[test]
+    fn test_security_rules() {
+        let yaml = r#"
+        services:
+          agent:
+            image: portainer/agent
+            privileged: true
+            cap_add: [NET_ADMIN, ALL]
+            network_mode: host
+            pid: host
+            security_opt:
+              - no-new-privileges:true
+              - seccomp:unconfined
+            user: "0:0"
+            volumes:
+              - /var/run/docker.sock:/var/run/docker.sock
+              - /:/host:ro
+              - type: bind
+                source: /etc/
+                target: /host/etc
+              - /etc/localtime:/etc/localtime:ro
+              - data:/data
+          web:
+            image: nginx
+            read_only: true
+            user: nginx
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let validator = rules().iter().fold(Validator::new(), |validator, rule| {
+            validator.enable(rule.id())
+        });
+        let errors = validator.check(&compose);
+        let reported: Vec<_> = errors
+            .all_errors()
+            .iter()
+            .filter(|e| e.code != ErrorCode::Deprecated)
+            .map(|e| (e.code.as_str(), e.path.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("privileged", "/services/agent/privileged"),
+                ("cap-add-all", "/services/agent/cap_add/1"),
+                ("host-network", "/services/agent/network_mode"),
+                ("host-pid", "/services/agent/pid"),
+                ("docker-socket", "/services/agent/volumes/0"),
+                ("sensitive-mount", "/services/agent/volumes/1"),
+                ("sensitive-mount", "/services/agent/volumes/2"),
+                ("seccomp-unconfined", "/services/agent/security_opt/1"),
+                ("read-only-root", "/services/agent/read_only"),
+                ("root-user", "/services/agent/user"),
+            ]
+        );
+
+        // None of them run by default
+        assert!(Validator::new().check(&compose).all_errors().is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+//! Validation against the compose-spec JSON Schema
+//!
+//! With the `compose-spec` feature, every document is checked against `schema/compose-spec.json`
+//! before the typed [`Compose`] is built. The schema follows the layout of the
+//! [compose-spec schema](https://github.com/compose-spec/compose-spec/blob/main/schema/compose-spec.json)
+//! and catches what the typed model cannot, such as misspelled keys. Violations are reported
+//! as [`ErrorCode::SchemaViolation`] errors alongside the semantic errors.
+//!
+//! To pick up changes to the specification, replace the vendored file with a newer copy.
+
+use std::sync::OnceLock;
+
+use jsonschema::Validator;
+use serde_yaml::Value;
+
+use crate::{
+    compose::Compose,
+    errors::{ErrorCode, Severity, ValidationError, ValidationErrors},
+    span::SourceMap,
+};
+
+const SCHEMA: &str = include_str!("../schema/compose-spec.json");
+
+fn validator() -> &'static Validator {
+    static VALIDATOR: OnceLock<Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        let schema = serde_json::from_str(SCHEMA).expect("the vendored schema is valid JSON");
+        jsonschema::validator_for(&schema).expect("the vendored schema is a valid JSON Schema")
+    })
+}
+
+/// Check the merged, interpolated `document` against the schema
+pub(crate) fn validate(document: &Value, source: &SourceMap) -> ValidationErrors {
+    let mut errors = ValidationErrors::new();
+    // Documents that cannot be represented as JSON, such as those with mappings as keys, are
+    // left to deserialization to report
+    let Ok(instance) = serde_json::to_value(document) else {
+        return errors;
+    };
+
+    for violation in validator().iter_errors(&instance) {
+        let mut error = ValidationError::new(
+            ErrorCode::SchemaViolation,
+            violation.instance_path().as_str(),
+            violation.to_string(),
+        );
+        match violation.instance().as_ref() {
+            serde_json::Value::String(s) => error = error.with_value(s),
+            value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_)) => {
+                error = error.with_value(value.to_string())
+            }
+            _ => (),
+        }
+        errors.add_error(error);
+    }
+    errors.locate(source);
+    errors
+}
+
+/// Report schema `violations` together with the errors of building the [`Compose`]
+///
+/// Where the typed model reported an error for the same node, its error is kept instead of
+/// the violation, since it is more specific and its code is stable across schema updates.
+/// When the [`Compose`] was built, its warnings are reported along with the violations.
+pub(crate) fn combine(
+    violations: ValidationErrors,
+    compose: Result<Compose, ValidationErrors>,
+) -> Result<Compose, ValidationErrors> {
+    if !violations.has_errors() {
+        return compose;
+    }
+    let errors = match compose {
+        Ok(compose) => {
+            let mut warnings = ValidationErrors::new();
+            warnings.extend(compose.warnings);
+            warnings
+        }
+        Err(errors) => errors,
+    };
+    let mut combined = ValidationErrors::new();
+    combined.extend(violations.into_iter().filter(|violation| {
+        !errors
+            .with_severity(Severity::Error)
+            .any(|error| error.path == violation.path)
+    }));
+    combined.extend(errors);
+    Err(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::{ErrorCode, Severity},
+        Compose,
+    };
+
+    #[test]
+    fn test_schema_violations() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            restart_policy: always
+            depends_on: [db]
+            cap_add: [NET_ADMIN, NET_ADMIN]
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let summary: Vec<_> = errors
+            .all_errors()
+            .iter()
+            .map(|e| (e.code, e.path.as_str()))
+            .collect();
+        assert!(summary.contains(&(ErrorCode::SchemaViolation, "/services/web")));
+        assert!(summary.contains(&(ErrorCode::SchemaViolation, "/services/web/cap_add")));
+        // Semantic errors are reported along with the schema violations
+        assert!(summary.contains(&(ErrorCode::UnknownService, "/services/web/depends_on/0")));
+
+        let error = errors
+            .all_errors()
+            .iter()
+            .find(|e| e.path == "/services/web")
+            .unwrap();
+        assert!(
+            error.message.contains("restart_policy"),
+            "{}",
+            error.message
+        );
+        assert_eq!(error.span.unwrap().start.line, 4);
+    }
+
+    #[test]
+    fn test_schema_violations_keep_warnings() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            mem_limit: 1g
+            cap_add: [NET_ADMIN, NET_ADMIN]
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let summary: Vec<_> = errors
+            .all_errors()
+            .iter()
+            .map(|e| (e.code, e.severity, e.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    ErrorCode::SchemaViolation,
+                    Severity::Error,
+                    "/services/web/cap_add"
+                ),
+                (
+                    ErrorCode::Deprecated,
+                    Severity::Warning,
+                    "/services/web/mem_limit"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_accepts_valid_documents() {
+        let yaml = r#"
+        name: shop
+        x-defaults: &defaults
+          restart: always
+        services:
+          web:
+            <<: *defaults
+            image: web
+            ports: ["8080:80", "9000"]
+            healthcheck:
+              interval: 30s
+            x-team: platform
+        volumes:
+          data:
+        "#;
+        Compose::new(yaml).unwrap();
+    }
+}
@@ -3,10 +3,12 @@
 use crate::compose::Compose;
 use serde::{Deserialize, Serialize};
 
-use crate::{compose::Validate, errors::ValidationErrors};
+use crate::{compose::Validate, errors::ValidationErrors, extensions::Extensions};
 
 /// Represents the top level [Secrets](https://docs.docker.com/compose/compose-file/09-secrets/) element
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Secret {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
@@ -19,13 +21,16 @@ pub struct Secret {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 impl Validate for Secret {
-    fn validate(&self, _: &Compose, _: &mut ValidationErrors) {
+    fn validate(&self, _: &Compose, _: &str, _: &mut ValidationErrors) {
         // Nothing to validate
         // Not interested in validating the existence of files on host
-        ()
     }
 }
 
@@ -45,9 +50,9 @@ mod tests {
 
         let secrets: Secret = serde_yaml::from_str(yaml).unwrap();
 
-        assert_eq!(secrets.file.unwrap(), "path/to/secret1".to_string());
-        assert_eq!(secrets.environment.unwrap(), "ENV_VAR".to_string());
-        assert_eq!(secrets.external.unwrap(), true);
-        assert_eq!(secrets.name.unwrap(), "named_secret".to_string());
+        assert_eq!(secrets.file.unwrap(), "path/to/secret1".to_owned());
+        assert_eq!(secrets.environment.unwrap(), "ENV_VAR".to_owned());
+        assert!(secrets.external.unwrap());
+        assert_eq!(secrets.name.unwrap(), "named_secret".to_owned());
     }
 }
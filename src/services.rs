@@ -1,25 +1,36 @@
 //! Service fields and validation
 
-mod blkio_config;
-mod build;
-mod deploy;
-mod healthcheck;
-mod logging;
-mod networks;
-mod ports;
-mod secrets;
-mod volumes;
-
-use crate::{compose::Compose, errors::ValidationError};
+pub mod blkio_config;
+pub mod build;
+pub mod deploy;
+pub mod healthcheck;
+pub mod logging;
+pub mod networks;
+pub mod ports;
+pub mod secrets;
+pub mod volumes;
+
+use crate::{
+    compose::Compose,
+    errors::{ErrorCode, ValidationError},
+    extends,
+    extensions::Extensions,
+    span::pointer,
+};
+use indexmap::IndexMap;
 use regex::Regex;
-use std::collections::HashMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{compose::Validate, errors::ValidationErrors};
+use crate::{
+    compose::{Normalize, Validate},
+    errors::ValidationErrors,
+};
 
 /// Represents the top level [Service](https://docs.docker.com/compose/compose-file/05-services/) element
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Service {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attach: Option<bool>,
@@ -190,7 +201,7 @@ pub struct Service {
     pub oom_score_adj: Option<i16>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub pid: Option<u32>,
+    pub pid: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pids_limit: Option<u32>,
@@ -199,7 +210,7 @@ pub struct Service {
     pub platform: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ports: Option<ports::Ports>,
+    pub ports: Option<Vec<ports::Ports>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub privileged: Option<bool>,
@@ -226,7 +237,7 @@ pub struct Service {
     pub secrets: Option<Vec<secrets::Secret>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub secruity_opt: Option<Vec<String>>,
+    pub security_opt: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub shm_size: Option<String>,
@@ -269,16 +280,22 @@ pub struct Service {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub working_dir: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Labels {
     List(Vec<String>),
-    Map(HashMap<String, String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Tmpfs {
     String(String),
@@ -286,6 +303,12 @@ pub enum Tmpfs {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "ServiceConfig"),
+    ts(rename = "ServiceConfig")
+)]
 #[serde(untagged)]
 pub enum Config {
     Short(String),
@@ -293,15 +316,30 @@ pub enum Config {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct ConfigDetails {
     pub source: String,
-    pub target: String,
-    pub uid: String,
-    pub gid: String,
-    pub mode: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum Cgroup {
     Host,
@@ -309,6 +347,7 @@ pub enum Cgroup {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Command {
     String(String),
@@ -316,6 +355,8 @@ pub enum Command {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct CredentialSpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
@@ -328,13 +369,16 @@ pub struct CredentialSpec {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum DependsOn {
     List(Vec<String>), // must be valid services
-    Map(HashMap<String, DependsOnDetail>),
+    Map(IndexMap<String, DependsOnDetail>),
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct DependsOnDetail {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restart: Option<bool>,
@@ -346,7 +390,8 @@ pub struct DependsOnDetail {
     pub required: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "snake_case")]
 pub enum DependsOnCondition {
     ServiceStarted,
@@ -354,14 +399,57 @@ pub enum DependsOnCondition {
     ServiceCompletedSuccessfully,
 }
 
+impl std::fmt::Display for DependsOnCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DependsOnCondition::ServiceStarted => write!(f, "service_started"),
+            DependsOnCondition::ServiceHealthy => write!(f, "service_healthy"),
+            DependsOnCondition::ServiceCompletedSuccessfully => {
+                write!(f, "service_completed_successfully")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Extends {
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
+pub enum Extends {
     // https://docs.docker.com/compose/compose-file/05-services/#extends
-    pub file: String,
+    /// A service in the same file
+    Short(String),
+    Long(ExtendsDetails),
+}
+
+impl Extends {
+    /// The name of the extended service
+    pub fn service(&self) -> &str {
+        match self {
+            Extends::Short(service) => service,
+            Extends::Long(details) => &details.service,
+        }
+    }
+
+    /// The file defining the extended service, if it is not the current one
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            Extends::Short(_) => None,
+            Extends::Long(details) => details.file.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
+pub struct ExtendsDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
     pub service: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum PullPolicy {
     Always,
@@ -371,6 +459,7 @@ pub enum PullPolicy {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum Restart {
     No,
@@ -380,18 +469,21 @@ pub enum Restart {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct Ulimits {
     pub nproc: u16,
     pub nofile: Nofile,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct Nofile {
     pub soft: u16,
     pub hard: u16,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Capabilities {
     All,
@@ -439,210 +531,404 @@ pub enum Capabilities {
 }
 
 impl Service {
-    fn validate_blkio_config(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.blkio_config.as_ref().map(|b| b.validate(ctx, errors));
+    /// Deserialize the `x-` extension field `key` into `T`, returning `None` if it is not set
+    pub fn extension<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, serde_yaml::Error> {
+        self.extensions.deserialize(key)
     }
 
-    fn validate_build(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.build.as_ref().map(|b| b.validate(ctx, errors));
+    /// Whether the service is enabled when `profiles` are selected
+    ///
+    /// Services without profiles are always enabled, others only when one of their profiles is
+    /// selected. `*` selects every profile.
+    pub fn is_enabled(&self, profiles: &[&str]) -> bool {
+        match &self.profiles {
+            Some(own) if !own.is_empty() => {
+                profiles.contains(&"*") || own.iter().any(|p| profiles.contains(&p.as_str()))
+            }
+            _ => true,
+        }
     }
 
-    fn validate_deploy(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.deploy.as_ref().map(|d| d.validate(ctx, errors));
+    /// The services this service depends on, along with the path token of each entry and
+    /// whether the dependency is required
+    pub(crate) fn dependencies(&self) -> Vec<(String, &str, bool)> {
+        match &self.depends_on {
+            Some(DependsOn::List(services)) => services
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (i.to_string(), s.as_str(), true))
+                .collect(),
+            Some(DependsOn::Map(services)) => services
+                .iter()
+                .map(|(s, detail)| (s.clone(), s.as_str(), detail.required.unwrap_or(true)))
+                .collect(),
+            None => Vec::new(),
+        }
     }
 
-    fn validate_healthcheck(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.healthcheck.as_ref().map(|h| h.validate(ctx, errors));
+    fn validate_blkio_config(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(b) = &self.blkio_config {
+            b.validate(ctx, &pointer(path, "blkio_config"), errors);
+        }
     }
 
-    fn validate_logging(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.logging.as_ref().map(|l| l.validate(ctx, errors));
+    fn validate_build(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(b) = &self.build {
+            b.validate(ctx, &pointer(path, "build"), errors);
+        }
     }
 
-    fn validate_networks(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.networks.as_ref().map(|n| n.validate(ctx, errors));
+    fn validate_deploy(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(d) = &self.deploy {
+            d.validate(ctx, &pointer(path, "deploy"), errors);
+        }
     }
 
-    fn validate_ports(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.ports.as_ref().map(|p| p.validate(ctx, errors));
+    fn validate_healthcheck(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(h) = &self.healthcheck {
+            h.validate(ctx, &pointer(path, "healthcheck"), errors);
+        }
     }
 
-    fn validate_secrets(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.secrets
-            .as_ref()
-            .map(|s| s.iter().for_each(|s| s.validate(ctx, errors)));
+    fn validate_logging(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(l) = &self.logging {
+            l.validate(ctx, &pointer(path, "logging"), errors);
+        }
     }
 
-    fn validate_volumes(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.volumes
-            .as_ref()
-            .map(|v| v.iter().for_each(|volume| volume.validate(ctx, errors)));
+    fn validate_networks(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(n) = &self.networks {
+            n.validate(ctx, &pointer(path, "networks"), errors);
+        }
     }
 
-    fn validate_configs(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        // configs must exist in top level configs
+    fn validate_ports(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(p) = &self.ports {
+            let path = pointer(path, "ports");
+            p.iter()
+                .enumerate()
+                .for_each(|(i, port)| port.validate(ctx, &pointer(&path, i), errors));
+        }
+    }
 
-        self.configs.as_ref().map(|c| {
-            c.iter().for_each(|config| match config {
-                Config::Short(c) => {
-                    ctx.configs.as_ref().map(|configs| {
-                        if !configs.contains_key(c) {
-                            errors.add_error(ValidationError::InvalidValue(format!(
-                                "Config is not defined: {}",
-                                c
-                            )))
-                        }
-                    });
+    fn validate_secrets(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(s) = &self.secrets {
+            let path = pointer(path, "secrets");
+            s.iter()
+                .enumerate()
+                .for_each(|(i, s)| s.validate(ctx, &pointer(&path, i), errors));
+        }
+    }
+
+    fn validate_volumes(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(v) = &self.volumes {
+            let path = pointer(path, "volumes");
+            v.iter()
+                .enumerate()
+                .for_each(|(i, volume)| volume.validate(ctx, &pointer(&path, i), errors));
+        }
+    }
+
+    fn validate_configs(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        // configs must exist in top level configs
+        let (Some(c), Some(configs)) = (&self.configs, &ctx.configs) else {
+            return;
+        };
+        let path = pointer(path, "configs");
+
+        c.iter().enumerate().for_each(|(i, config)| match config {
+            Config::Short(c) => {
+                if !configs.contains_key(c) {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::UnknownConfig,
+                            &pointer(&path, i),
+                            format!("Config is not defined: {}", c),
+                        )
+                        .with_value(c),
+                    )
                 }
-                Config::Long(c) => {
-                    ctx.configs.as_ref().map(|configs| {
-                        if !configs.contains_key(&c.source) {
-                            errors.add_error(ValidationError::InvalidValue(format!(
-                                "Config is not defined: {}",
-                                &c.source
-                            )))
-                        }
-                    });
+            }
+            Config::Long(c) => {
+                if !configs.contains_key(&c.source) {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::UnknownConfig,
+                            &pointer(&pointer(&path, i), "source"),
+                            format!("Config is not defined: {}", &c.source),
+                        )
+                        .with_value(&c.source),
+                    )
                 }
-            })
+            }
         });
     }
 
-    fn validate_credential_spec(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.credential_spec.as_ref().map(|c| {
-            let result = c.config.as_ref().map(|cred| {
-                ctx.configs
-                    .as_ref()
-                    .map(|configs| configs.contains_key(cred))
-                    .is_some()
-            });
-            result.map(|r| {
-                if !r {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Credential Spec references unknown config".to_string(),
-                    ))
-                }
-            });
-        });
+    fn validate_credential_spec(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(cred) = self
+            .credential_spec
+            .as_ref()
+            .and_then(|c| c.config.as_ref())
+        {
+            let known = ctx
+                .configs
+                .as_ref()
+                .is_some_and(|configs| configs.contains_key(cred));
+            if !known {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::UnknownConfig,
+                        &pointer(&pointer(path, "credential_spec"), "config"),
+                        "Credential Spec references unknown config",
+                    )
+                    .with_value(cred),
+                )
+            }
+        }
     }
 
-    fn validate_container_name(&self, _: &Compose, errors: &mut ValidationErrors) {
+    fn validate_container_name(&self, _: &Compose, path: &str, errors: &mut ValidationErrors) {
         let re = Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.-]+$").unwrap();
-        self.container_name.as_ref().map(|c| {
+        if let Some(c) = &self.container_name {
             if !re.is_match(c) {
-                errors.add_error(ValidationError::InvalidValue(
-                    "Invalid container name".to_string(),
-                ));
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::InvalidContainerName,
+                        &pointer(path, "container_name"),
+                        "Invalid container name",
+                    )
+                    .with_value(c),
+                );
             }
-        });
+        }
     }
 
-    fn validate_depends_on(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.depends_on.as_ref().map(|d| match d {
-            DependsOn::List(service) => {
-                let result = service.iter().any(|s| ctx.services.contains_key(s));
-                if !result {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Invalid service for depends_on".to_string(),
-                    ));
-                }
+    fn validate_depends_on(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        let path = pointer(path, "depends_on");
+        for (token, service, _) in self.dependencies() {
+            if !ctx.services.contains_key(service) {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::UnknownService,
+                        &pointer(&path, token),
+                        "Invalid service for depends_on",
+                    )
+                    .with_value(service),
+                );
             }
-            DependsOn::Map(service) => {
-                let result = service.iter().any(|s| ctx.services.contains_key(s.0));
-                if !result {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Invalid service for depends_on".to_string(),
-                    ));
+        }
+    }
+
+    fn validate_profiles(&self, _: &Compose, path: &str, errors: &mut ValidationErrors) {
+        let re = Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.-]+$").unwrap();
+        if let Some(profiles) = &self.profiles {
+            let path = pointer(path, "profiles");
+            for (i, profile) in profiles.iter().enumerate() {
+                if !re.is_match(profile) {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::InvalidProfile,
+                            &pointer(&path, i),
+                            "Invalid profile name",
+                        )
+                        .with_value(profile),
+                    );
                 }
             }
-        });
+        }
     }
 
-    fn validate_expose(&self, _: &Compose, errors: &mut ValidationErrors) {
-        self.expose.as_ref().map(|e| {
-            e.iter().all(|port| match port.parse::<u16>() {
-                Err(_) => {
-                    errors.add_error(ValidationError::InvalidValue("Invalid port".to_string()));
-                    false
+    fn validate_expose(&self, _: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if let Some(e) = &self.expose {
+            let path = pointer(path, "expose");
+            e.iter().enumerate().for_each(|(i, port)| {
+                if port.parse::<u16>().is_err() {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::InvalidPort,
+                            &pointer(&path, i),
+                            "Invalid port",
+                        )
+                        .with_value(port),
+                    );
                 }
-                _ => true,
+            });
+        }
+    }
+
+    fn validate_extends(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        let Some(e) = &self.extends else {
+            return;
+        };
+        // Services from other files can only be checked once they are loaded, see
+        // `Compose::resolve_extends`
+        if e.file().is_some() {
+            return;
+        }
+        let path = match e {
+            Extends::Short(_) => pointer(path, "extends"),
+            Extends::Long(_) => pointer(&pointer(path, "extends"), "service"),
+        };
+
+        let Some(service) = ctx.services.get(e.service()) else {
+            errors.add_error(
+                ValidationError::new(
+                    ErrorCode::UnknownService,
+                    &path,
+                    "Extends references invalid service",
+                )
+                .with_value(e.service()),
+            );
+            return;
+        };
+
+        // Services that depend on other services cannot be used as a base, the same
+        // restrictions apply when `extends` is resolved
+        let Ok(service) = serde_yaml::to_value(service) else {
+            return;
+        };
+        for message in extends::restrictions(&service) {
+            errors.add_error(
+                ValidationError::new(ErrorCode::InvalidExtends, &path, message)
+                    .with_value(e.service()),
+            );
+        }
+    }
+}
+
+impl Normalize for Service {
+    fn normalize(&mut self) {
+        if let Some(b) = &mut self.build {
+            b.normalize();
+        }
+        if let Some(d) = &mut self.depends_on {
+            d.normalize();
+        }
+        for labels in [
+            &mut self.environment,
+            &mut self.labels,
+            &mut self.annotations,
+            &mut self.sysctls,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            labels.normalize();
+        }
+        if let Some(n) = &mut self.networks {
+            n.normalize();
+        }
+        if let Some(p) = &mut self.ports {
+            p.normalize();
+        }
+        self.volumes
+            .iter_mut()
+            .flatten()
+            .for_each(Normalize::normalize);
+        self.configs
+            .iter_mut()
+            .flatten()
+            .for_each(Normalize::normalize);
+        self.secrets
+            .iter_mut()
+            .flatten()
+            .for_each(Normalize::normalize);
+    }
+}
+
+impl Normalize for Labels {
+    /// Lists of `KEY=VALUE` strings become mappings. Lists with keys that have no value are
+    /// left as they are, since the value comes from the host environment.
+    fn normalize(&mut self) {
+        let Labels::List(list) = self else {
+            return;
+        };
+        let map: Option<IndexMap<String, String>> = list
+            .iter()
+            .map(|entry| {
+                let (key, value) = entry.split_once('=')?;
+                Some((key.to_owned(), value.to_owned()))
             })
-        });
+            .collect();
+        if let Some(map) = map {
+            *self = Labels::Map(map);
+        }
     }
+}
 
-    fn validate_extends(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.extends.as_ref().map(|e| {
-            let result = ctx.services.contains_key(&e.service);
-            if result {
-                // Services that have dependencies on other services cannot be used as a base.
-                // Therefore, any key that introduces a dependency on another service is incompatible
-                // with extends. The non-exhaustive list of such keys is: links, volumes_from, container
-                // mode (in ipc, pid, network_mode and net), service mode (in ipc, pid and network_mode), depends_on.
-                let service = ctx.services.get(&e.service).unwrap();
-                service.depends_on.as_ref().map(|_| {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Extends cannot extend another service that has a depends_on".to_string(),
-                    ))
-                });
-                service.links.as_ref().map(|l| {
-                    if l.len() > 0 {
-                        errors.add_error(ValidationError::InvalidValue(
-                            "Extends cannot have any links".to_string(),
-                        ))
-                    }
-                });
-                service.volumes_from.as_ref().map(|v| {
-                    if v.len() > 0 {
-                        errors.add_error(ValidationError::InvalidValue(
-                            "Extends cannot have any volumes_from".to_string(),
-                        ))
-                    }
-                });
-                service.ipc.as_ref().map(|_| {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Extends cannot have an IPC mode".to_string(),
-                    ))
-                });
-                service.network_mode.as_ref().map(|n| {
-                    if n.starts_with("service:") {
-                        errors.add_error(ValidationError::InvalidValue(
-                            "Extends cannot extend a service that has a network dependency"
-                                .to_string(),
-                        ))
-                    }
-                });
-            } else {
-                errors.add_error(ValidationError::InvalidValue(
-                    "Extends references invalid service".to_string(),
-                ));
+impl Normalize for DependsOn {
+    fn normalize(&mut self) {
+        if let DependsOn::List(services) = self {
+            *self = DependsOn::Map(
+                services
+                    .iter()
+                    .map(|s| (s.clone(), DependsOnDetail::default()))
+                    .collect(),
+            );
+        }
+        if let DependsOn::Map(services) = self {
+            for detail in services.values_mut() {
+                detail
+                    .condition
+                    .get_or_insert(DependsOnCondition::ServiceStarted);
+                detail.required.get_or_insert(true);
             }
+        }
+    }
+}
+
+impl Normalize for Config {
+    fn normalize(&mut self) {
+        let long = match self {
+            Config::Short(source) => ConfigDetails {
+                source: source.clone(),
+                target: None,
+                uid: None,
+                gid: None,
+                mode: None,
+                extensions: Extensions::default(),
+            },
+            Config::Long(long) => long.clone(),
+        };
+        let target = long
+            .target
+            .clone()
+            .unwrap_or_else(|| format!("/{}", long.source));
+        *self = Config::Long(ConfigDetails {
+            target: Some(target),
+            ..long
         });
     }
 }
 
 impl Validate for Service {
-    fn validate(&self, ctx: &Compose, errors: &mut ValidationErrors) {
-        self.validate_blkio_config(ctx, errors);
-        self.validate_build(ctx, errors);
-        self.validate_deploy(ctx, errors);
-        self.validate_healthcheck(ctx, errors);
-        self.validate_logging(ctx, errors);
-        self.validate_networks(ctx, errors);
-        self.validate_ports(ctx, errors);
-        self.validate_secrets(ctx, errors);
-        self.validate_volumes(ctx, errors);
-        self.validate_configs(ctx, errors);
-        self.validate_container_name(ctx, errors);
-        self.validate_credential_spec(ctx, errors);
-        self.validate_depends_on(ctx, errors);
-        self.validate_expose(ctx, errors);
-        self.validate_extends(ctx, errors);
+    fn validate(&self, ctx: &Compose, path: &str, errors: &mut ValidationErrors) {
+        self.validate_blkio_config(ctx, path, errors);
+        self.validate_build(ctx, path, errors);
+        self.validate_deploy(ctx, path, errors);
+        self.validate_healthcheck(ctx, path, errors);
+        self.validate_logging(ctx, path, errors);
+        self.validate_networks(ctx, path, errors);
+        self.validate_ports(ctx, path, errors);
+        self.validate_secrets(ctx, path, errors);
+        self.validate_volumes(ctx, path, errors);
+        self.validate_configs(ctx, path, errors);
+        self.validate_container_name(ctx, path, errors);
+        self.validate_credential_spec(ctx, path, errors);
+        self.validate_depends_on(ctx, path, errors);
+        self.validate_expose(ctx, path, errors);
+        self.validate_extends(ctx, path, errors);
+        self.validate_profiles(ctx, path, errors);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::Severity;
 
     #[test]
     fn depends_on_missing_service() {
@@ -730,4 +1016,104 @@ mod tests {
         dbg!(&compose);
         assert!(compose.is_err());
     }
+
+    #[test]
+    fn credential_spec_unknown_config() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            credential_spec:
+              config: missing
+        configs:
+          my_config:
+            file: ./my_config.txt
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let error = &errors.all_errors()[0];
+        assert_eq!(error.code, ErrorCode::UnknownConfig);
+        assert_eq!(error.path, "/services/web/credential_spec/config");
+        assert_eq!(error.value.as_deref(), Some("missing"));
+
+        assert!(Compose::new(&yaml.replace("config: missing", "config: my_config")).is_ok());
+    }
+
+    #[test]
+    fn invalid_expose_span() {
+        let yaml = r#"
+        services:
+          gitlab:
+            image: gitlab/gitlab-ce:latest
+            expose:
+              - "3000"
+              - "not a port"
+        "#;
+
+        let compose = Compose::new(yaml);
+        let errors = compose.unwrap_err();
+        let span = errors.all_errors()[0].span.unwrap();
+        assert_eq!((span.start.line, span.start.column), (7, 17));
+        assert_eq!(&yaml[span.start.offset..span.end.offset], "\"not a port\"");
+    }
+
+    #[test]
+    fn depends_on_missing_service_path() {
+        let yaml = r#"
+        services:
+          gitlab:
+            image: gitlab/gitlab-ce:latest
+            depends_on:
+              - postgres
+              - redis
+          postgres:
+            image: postgres:latest
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let error = errors.with_code(ErrorCode::UnknownService).next().unwrap();
+        assert_eq!(error.code.as_str(), "CV0006");
+        assert_eq!(error.path, "/services/gitlab/depends_on/1");
+        assert_eq!(error.value.as_deref(), Some("redis"));
+        assert_eq!(errors.under("/services/gitlab").count(), 1);
+    }
+
+    #[test]
+    fn deprecated_fields_are_warnings() {
+        let yaml = r#"
+        version: "3.9"
+        services:
+          gitlab:
+            image: gitlab/gitlab-ce:latest
+            cpus: 0.5
+            mem_limit: 1g
+            scale: 2
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let warnings: Vec<_> = compose
+            .warnings
+            .iter()
+            .map(|w| (w.severity, w.path.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (Severity::Warning, "/services/gitlab/cpus"),
+                (Severity::Warning, "/services/gitlab/mem_limit"),
+                (Severity::Warning, "/services/gitlab/scale"),
+                (Severity::Warning, "/version"),
+            ]
+        );
+        assert_eq!(compose.warnings[0].span.unwrap().start.line, 6);
+        assert_eq!(
+            compose.warnings[2].message,
+            "scale is deprecated, use deploy.replicas instead"
+        );
+
+        // Warnings are reported along with errors when the manifest is invalid
+        let errors =
+            Compose::new(&yaml.replace("latest", "latest\n            expose: [x]")).unwrap_err();
+        assert_eq!(errors.with_severity(Severity::Warning).count(), 4);
+    }
 }
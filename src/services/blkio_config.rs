@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use crate::compose::{Compose, Validate};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct BlkioConfig {
     pub weight: u16,
 
@@ -23,44 +25,49 @@ pub struct BlkioConfig {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct WeightDevice {
     pub path: String,
     pub weight: u16,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct DeviceReadBps {
     pub path: String,
     pub rate: Rate,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct DeviceWriteBps {
     pub path: String,
     pub rate: Rate,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct DeviceReadIops {
     pub path: String,
     pub rate: Rate,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct DeviceWriteIops {
     pub path: String,
     pub rate: Rate,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum Rate {
     String(String),
     Bytes(u64),
 }
 
 impl Validate for BlkioConfig {
-    fn validate(&self, _: &Compose, _: &mut crate::errors::ValidationErrors) {
+    fn validate(&self, _: &Compose, _: &str, _: &mut crate::errors::ValidationErrors) {
         // Not interested in verifying host devices
-        ()
     }
 }
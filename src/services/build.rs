@@ -1,14 +1,18 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    compose::{Compose, Validate},
-    errors::ValidationError,
+    compose::{Compose, Normalize, Validate},
+    errors::{ErrorCode, ValidationError},
+    extensions::Extensions,
+    span::pointer,
 };
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Build {
     String(String),
 
@@ -16,7 +20,9 @@ pub enum Build {
     Map(BuildDetails),
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct BuildDetails {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<String>,
@@ -74,39 +80,58 @@ pub struct BuildDetails {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub platforms: Option<Vec<String>>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
 pub enum BuildArgs {
     List(Vec<String>),
-    Map(HashMap<String, String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
 pub enum SshArgs {
     List(Vec<String>),
-    Map(HashMap<String, String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(untagged)]
 pub enum AdditionalContexts {
     List(Vec<String>),
-    Map(HashMap<String, String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "BuildLabels"),
+    ts(rename = "BuildLabels")
+)]
+#[serde(untagged)]
 pub enum Labels {
     List(Vec<String>),
-    Map(HashMap<String, String>),
+    Map(IndexMap<String, String>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum ShmSize {
     String(String),
     Bytes(u64),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum BuildSecret {
     Short(String),
@@ -114,6 +139,7 @@ pub enum BuildSecret {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct SecretDetails {
     pub source: String,
     pub target: String,
@@ -122,50 +148,69 @@ pub struct SecretDetails {
     pub mode: String,
 }
 
-impl Validate for Build {
-    fn validate(&self, ctx: &Compose, errors: &mut crate::errors::ValidationErrors) {
-        // Check that specified secrets exist
-        match self {
-            Build::Map(details) => {
-                details.secrets.as_ref().map(|s| {
-                    s.iter().for_each(|secret| match secret {
-                        BuildSecret::Short(short) => {
-                            ctx.secrets.as_ref().map(|x| {
-                                if !x.contains_key(short) {
-                                    errors.add_error(ValidationError::InvalidValue(format!(
-                                        "Secret is not defined: {}",
-                                        short
-                                    )))
-                                }
-                            });
-                        }
-                        BuildSecret::Long(details) => {
-                            ctx.secrets.as_ref().map(|x| {
-                                if !x.contains_key(&details.source) {
-                                    errors.add_error(ValidationError::InvalidValue(format!(
-                                        "Secret is not defined: {}",
-                                        details.source
-                                    )))
-                                }
-                            });
-                        }
-                    })
-                });
+impl Normalize for Build {
+    fn normalize(&mut self) {
+        if let Build::String(context) = self {
+            *self = Build::Map(BuildDetails {
+                context: Some(context.clone()),
+                ..BuildDetails::default()
+            });
+        }
+        if let Build::Map(details) = self {
+            details.context.get_or_insert_with(|| ".".to_owned());
+            if details.dockerfile_inline.is_none() {
+                details
+                    .dockerfile
+                    .get_or_insert_with(|| "Dockerfile".to_owned());
             }
-            _ => (),
         }
+    }
+}
 
-        match self {
-            Build::Map(details) => {
-                if details.dockerfile_inline.is_some() && details.dockerfile.is_some() {
-                    errors.add_error(ValidationError::InvalidValue(
-                        "Cannot specify a Dockerfile and an inline Dockerfile".to_string(),
-                    ))
+impl Validate for Build {
+    fn validate(&self, ctx: &Compose, path: &str, errors: &mut crate::errors::ValidationErrors) {
+        let Build::Map(details) = self else {
+            return;
+        };
+
+        // Check that specified secrets exist
+        if let (Some(s), Some(x)) = (&details.secrets, &ctx.secrets) {
+            let path = pointer(path, "secrets");
+            s.iter().enumerate().for_each(|(i, secret)| match secret {
+                BuildSecret::Short(short) => {
+                    if !x.contains_key(short) {
+                        errors.add_error(
+                            ValidationError::new(
+                                ErrorCode::UnknownSecret,
+                                &pointer(&path, i),
+                                format!("Secret is not defined: {}", short),
+                            )
+                            .with_value(short),
+                        )
+                    }
                 }
-            }
-            _ => (),
+                BuildSecret::Long(details) => {
+                    if !x.contains_key(&details.source) {
+                        errors.add_error(
+                            ValidationError::new(
+                                ErrorCode::UnknownSecret,
+                                &pointer(&pointer(&path, i), "source"),
+                                format!("Secret is not defined: {}", details.source),
+                            )
+                            .with_value(&details.source),
+                        )
+                    }
+                }
+            });
+        }
+
+        if details.dockerfile_inline.is_some() && details.dockerfile.is_some() {
+            errors.add_error(ValidationError::new(
+                ErrorCode::ConflictingDockerfile,
+                &pointer(path, "dockerfile_inline"),
+                "Cannot specify a Dockerfile and an inline Dockerfile",
+            ))
         }
-        ()
     }
 }
 
@@ -191,6 +236,51 @@ mod tests {
         assert!(compose.is_ok())
     }
 
+    #[test]
+    fn test_build_lists_and_maps() {
+        let yaml = r#"
+        services:
+          web:
+            build:
+              context: .
+              ssh:
+                - default
+              additional_contexts:
+                resources: /path/to/resources
+              labels:
+                com.example.description: Web app
+          worker:
+            build:
+              context: .
+              ssh:
+                myproject: ~/.ssh/myproject.pem
+              additional_contexts:
+                - resources=/path/to/resources
+              labels:
+                - com.example.description=Worker
+        "#;
+
+        let compose = Compose::new(yaml).unwrap();
+        let Some(Build::Map(web)) = &compose.services["web"].build else {
+            panic!("build should be a map");
+        };
+        assert!(matches!(web.ssh, Some(SshArgs::List(_))));
+        assert!(matches!(
+            web.additional_contexts,
+            Some(AdditionalContexts::Map(_))
+        ));
+        assert!(matches!(web.labels, Some(Labels::Map(_))));
+        let Some(Build::Map(worker)) = &compose.services["worker"].build else {
+            panic!("build should be a map");
+        };
+        assert!(matches!(worker.ssh, Some(SshArgs::Map(_))));
+        assert!(matches!(
+            worker.additional_contexts,
+            Some(AdditionalContexts::List(_))
+        ));
+        assert!(matches!(worker.labels, Some(Labels::List(_))));
+    }
+
     #[test]
     fn test_invalid_build_inline() {
         let yaml = r#"
@@ -1,15 +1,20 @@
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Validate},
+    extensions::Extensions,
+};
 
 use super::Labels;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Deploy {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub replicas: Option<u16>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub enpoint_mode: Option<EndpointMode>,
+    pub endpoint_mode: Option<EndpointMode>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<Labels>,
@@ -31,9 +36,15 @@ pub struct Deploy {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update_config: Option<UpdateConfig>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Placement {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub constraints: Option<Labels>,
@@ -43,6 +54,7 @@ pub struct Placement {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum EndpointMode {
     Vip,
@@ -50,6 +62,12 @@ pub enum EndpointMode {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "DeployMode"),
+    ts(rename = "DeployMode")
+)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
     Global,
@@ -57,6 +75,8 @@ pub enum Mode {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Resources {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limits: Option<Limits>,
@@ -66,6 +86,8 @@ pub struct Resources {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Limits {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpus: Option<String>,
@@ -84,6 +106,7 @@ pub struct Limits {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum DriverCapabilities {
     Gpu,
@@ -91,6 +114,8 @@ pub enum DriverCapabilities {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct RestartPolicy {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub condition: Option<RestartCondition>,
@@ -106,6 +131,7 @@ pub struct RestartPolicy {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "snake_case")]
 pub enum RestartCondition {
     None,
@@ -114,12 +140,15 @@ pub enum RestartCondition {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct Reservations {
     pub cpus: String,
     pub memory: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct RollbackConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parallelism: Option<u8>,
@@ -141,6 +170,8 @@ pub struct RollbackConfig {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct UpdateConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parallelism: Option<u8>,
@@ -162,6 +193,7 @@ pub struct UpdateConfig {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "kebab-case")]
 pub enum Order {
     StopFirst,
@@ -169,6 +201,7 @@ pub enum Order {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum FailureAction {
     Continue,
@@ -176,8 +209,7 @@ pub enum FailureAction {
 }
 
 impl Validate for Deploy {
-    fn validate(&self, _: &Compose, _: &mut crate::errors::ValidationErrors) {
+    fn validate(&self, _: &Compose, _: &str, _: &mut crate::errors::ValidationErrors) {
         // Nothing to really validate here
-        ()
     }
 }
@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Validate},
+    extensions::Extensions,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct HealthCheck {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub test: Option<Test>,
@@ -24,16 +29,19 @@ pub struct HealthCheck {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub disable: Option<bool>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum Test {
     String(String),
     List(Vec<String>),
 }
 
 impl Validate for HealthCheck {
-    fn validate(&self, _: &Compose, _: &mut crate::errors::ValidationErrors) {
-        ()
-    }
+    fn validate(&self, _: &Compose, _: &str, _: &mut crate::errors::ValidationErrors) {}
 }
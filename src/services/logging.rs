@@ -1,23 +1,31 @@
 use serde::{Deserialize, Serialize};
 
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Validate},
+    extensions::Extensions,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Logging {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub driver: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<Options>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct Options {
     pub syslog_address: String,
 }
 
 impl Validate for Logging {
-    fn validate(&self, _: &Compose, _: &mut crate::errors::ValidationErrors) {
-        ()
-    }
+    fn validate(&self, _: &Compose, _: &str, _: &mut crate::errors::ValidationErrors) {}
 }
@@ -1,17 +1,25 @@
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Normalize, Validate},
+    errors::{ErrorCode, ValidationError},
+    extensions::Extensions,
+    span::pointer,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Networks {
     List(Vec<String>),
-    Map(HashMap<String, NetworkOptions>),
+    Map(IndexMap<String, NetworkOptions>),
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct NetworkOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aliases: Option<Vec<String>>,
@@ -27,33 +35,50 @@ pub struct NetworkOptions {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<u16>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 impl Validate for Networks {
-    fn validate(&self, ctx: &Compose, errors: &mut crate::errors::ValidationErrors) {
-        match self {
-            Networks::List(n) => {
-                ctx.networks.as_ref().map(|available_networks| {
-                    n.iter().for_each(|network| {
-                        if !available_networks.contains_key(network) {
-                            errors.add_error(crate::errors::ValidationError::InvalidValue(
-                                format!("Service networks reference an unknown network: {network}"),
-                            ));
-                        }
-                    });
-                });
-            }
-            Networks::Map(n) => {
-                ctx.networks.as_ref().map(|available_networks| {
-                    n.keys().for_each(|network| {
-                        if !available_networks.contains_key(network) {
-                            errors.add_error(crate::errors::ValidationError::InvalidValue(
-                                format!("Service networks reference an unknown network: {network}"),
-                            ));
-                        }
-                    });
-                });
+    fn validate(&self, ctx: &Compose, path: &str, errors: &mut crate::errors::ValidationErrors) {
+        let Some(available_networks) = &ctx.networks else {
+            return;
+        };
+        let mut check = |network: &String, path: String| {
+            if !available_networks.contains_key(network) {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::UnknownNetwork,
+                        &path,
+                        format!("Service networks reference an unknown network: {network}"),
+                    )
+                    .with_value(network),
+                );
             }
+        };
+        match self {
+            Networks::List(n) => n
+                .iter()
+                .enumerate()
+                .for_each(|(i, network)| check(network, pointer(path, i))),
+            Networks::Map(n) => n
+                .keys()
+                .for_each(|network| check(network, pointer(path, network))),
+        }
+    }
+}
+
+impl Normalize for Networks {
+    fn normalize(&mut self) {
+        if let Networks::List(networks) = self {
+            *self = Networks::Map(
+                networks
+                    .iter()
+                    .map(|n| (n.clone(), NetworkOptions::default()))
+                    .collect(),
+            );
         }
     }
 }
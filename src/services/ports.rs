@@ -1,37 +1,954 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
-use crate::compose::{Compose, Validate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::{
+    compose::{Compose, Normalize, Validate},
+    errors::{ErrorCode, ValidationError, ValidationErrors},
+    extensions::Extensions,
+    span::pointer,
+};
+
+/// An entry of a service's `ports`, in either the short or the long syntax
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Ports {
-    Short(Vec<String>),
-    Long(Vec<PortOptions>),
+    /// A short syntax entry, which may be written as a number such as `3000`
+    Short(
+        #[serde(with = "string_or_number")]
+        #[cfg_attr(
+            feature = "typegen",
+            schemars(with = "StringOrNumber"),
+            ts(type = "string | number")
+        )]
+        String,
+    ),
+    Long(PortOptions),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct PortOptions {
     pub target: u16,
-    pub host_ip: String,
-    pub published: String,
-    pub protocol: Protocol,
-    pub mode: Mode,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_ip: Option<String>,
+
+    /// A port or a range of ports, which may be written as a number such as `8080`
+    #[serde(
+        default,
+        with = "optional_string_or_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(
+        feature = "typegen",
+        schemars(with = "Option<StringOrNumber>"),
+        ts(type = "string | number")
+    )]
+    pub published: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<Protocol>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<Mode>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
 pub enum Protocol {
     Udp,
     Tcp,
+    Sctp,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "PortMode"),
+    ts(rename = "PortMode")
+)]
+#[serde(rename_all = "lowercase")]
 pub enum Mode {
     Host,
     Ingress,
 }
 
+/// A port written either as a string or as a number
+#[derive(Deserialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(u64),
+}
+
+impl From<StringOrNumber> for String {
+    fn from(value: StringOrNumber) -> Self {
+        match value {
+            StringOrNumber::String(s) => s,
+            StringOrNumber::Number(n) => n.to_string(),
+        }
+    }
+}
+
+mod string_or_number {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(value)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<String, D::Error> {
+        StringOrNumber::deserialize(deserializer).map(String::from)
+    }
+}
+
+mod optional_string_or_number {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(
+        value: &Option<String>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        Option::<StringOrNumber>::deserialize(deserializer).map(|value| value.map(String::from))
+    }
+}
+
 impl Validate for Ports {
-    fn validate(&self, _: &Compose, _: &mut crate::errors::ValidationErrors) {
-        ()
+    fn validate(&self, _: &Compose, path: &str, errors: &mut ValidationErrors) {
+        if self.is_interpolated() {
+            return;
+        }
+        let result = match self {
+            Ports::Short(port) => port.parse::<PortMapping>().map(drop),
+            Ports::Long(port) => PortMapping::try_from(port).map(drop),
+        };
+        if let Err(mut error) = result {
+            error.path = path.to_owned();
+            errors.add_error(error);
+        }
+    }
+}
+
+impl Ports {
+    /// Whether the entry still references variables
+    fn is_interpolated(&self) -> bool {
+        match self {
+            Ports::Short(port) => port.contains('$'),
+            Ports::Long(port) => [&port.host_ip, &port.published]
+                .into_iter()
+                .flatten()
+                .any(|value| value.contains('$')),
+        }
+    }
+
+    /// The parsed entry, unless it is invalid
+    ///
+    /// Entries with `${VARIABLE}` references are left out, as the manifest is not interpolated.
+    fn mapping(&self) -> Option<PortMapping> {
+        if self.is_interpolated() {
+            return None;
+        }
+        match self {
+            Ports::Short(port) => PortMapping::parse(port).ok(),
+            Ports::Long(port) => PortMapping::try_from(port).ok(),
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+            Protocol::Sctp => write!(f, "sctp"),
+        }
+    }
+}
+
+/// A port or an inclusive range of ports, e.g. `8080` or `8080-8081`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    start: u16,
+    end: u16,
+}
+
+impl PortRange {
+    /// The ports from `start` to `end`, unless `start` is 0 or comes after `end`
+    pub fn new(start: u16, end: u16) -> Option<Self> {
+        (start != 0 && start <= end).then_some(PortRange { start, end })
+    }
+
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// The number of ports in the range
+    pub fn count(&self) -> usize {
+        usize::from(self.end - self.start) + 1
+    }
+
+    pub fn ports(&self) -> RangeInclusive<u16> {
+        self.start..=self.end
+    }
+
+    /// Whether the ranges have a port in common
+    pub fn overlaps(&self, other: &PortRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// Parse a port or a `START-END` range, each between 1 and 65535
+    fn parse(range: &str) -> Result<Self, String> {
+        let port = |port: &str| match port.parse::<u32>() {
+            Ok(port @ 1..=65535) => Ok(port as u16),
+            Ok(port) => Err(format!("Port {port} is out of range, expected 1 to 65535")),
+            Err(_) => Err(format!("Invalid port {port}")),
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (port(start)?, port(end)?),
+            None => (port(range)?, port(range)?),
+        };
+        PortRange::new(start, end)
+            .ok_or_else(|| format!("Invalid port range {range}, it ends before it starts"))
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// An entry of the short port syntax `[[HOST_IP:]PUBLISHED:]TARGET[/PROTOCOL]`
+///
+/// Both ports can be ranges, such as `127.0.0.1:8080-8081:80-81/udp`. An IPv6 host IP goes in
+/// brackets, as in `[::1]:80:80`, and the published port can be left empty to have Compose pick
+/// one on that address, as in `127.0.0.1::80`. A target range is published onto a published range of the
+/// same length, while a single target is published on any free port of a published range.
+///
+/// ```
+/// use compose_validatr::services::ports::{PortMapping, PortRange, Protocol};
+///
+/// let mapping: PortMapping = "127.0.0.1:8080-8081:80-81/udp".parse().unwrap();
+/// assert_eq!(mapping.published_range, PortRange::new(8080, 8081));
+/// assert_eq!(mapping.protocol, Protocol::Udp);
+/// assert_eq!(mapping.to_long().len(), 2);
+///
+/// assert!("8080-8082:80-81".parse::<PortMapping>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortMapping {
+    /// Every address when not set
+    pub host_ip: Option<IpAddr>,
+
+    /// A free host port is picked when not set
+    pub published_range: Option<PortRange>,
+
+    pub target_range: PortRange,
+
+    pub protocol: Protocol,
+}
+
+impl PortMapping {
+    fn parse(port: &str) -> Result<Self, String> {
+        let (port, protocol) = match port.rsplit_once('/') {
+            Some((port, "tcp")) => (port, Protocol::Tcp),
+            Some((port, "udp")) => (port, Protocol::Udp),
+            Some((port, "sctp")) => (port, Protocol::Sctp),
+            Some((_, protocol)) => {
+                return Err(format!(
+                    "Invalid protocol {protocol}, expected tcp, udp or sctp"
+                ))
+            }
+            None => (port, Protocol::Tcp),
+        };
+
+        let (host_ip, port) = match port.strip_prefix('[') {
+            Some(rest) => {
+                let (ip, port) = rest
+                    .split_once("]:")
+                    .ok_or_else(|| format!("Invalid host IP in {port}"))?;
+                let ip = ip
+                    .parse::<Ipv6Addr>()
+                    .map_err(|_| format!("Invalid IPv6 host IP {ip}"))?;
+                (Some(IpAddr::V6(ip)), port)
+            }
+            None => match port.matches(':').count() {
+                0 | 1 => (None, port),
+                2 => {
+                    let (ip, port) = port.split_once(':').unwrap_or_default();
+                    let ip = match ip {
+                        "" => None,
+                        ip => Some(
+                            ip.parse::<Ipv4Addr>()
+                                .map_err(|_| format!("Invalid host IP {ip}"))?
+                                .into(),
+                        ),
+                    };
+                    (ip, port)
+                }
+                _ => {
+                    return Err(
+                        "Invalid port, an IPv6 host IP must be in brackets, as in [::1]:80:80"
+                            .to_owned(),
+                    )
+                }
+            },
+        };
+
+        let (published, target) = match port.rsplit_once(':') {
+            Some(("", target)) => (None, target),
+            Some((published, target)) => (Some(published), target),
+            None => (None, port),
+        };
+        let mapping = PortMapping {
+            host_ip,
+            published_range: published.map(PortRange::parse).transpose()?,
+            target_range: PortRange::parse(target)?,
+            protocol,
+        };
+        mapping.check_ranges()?;
+        Ok(mapping)
+    }
+
+    /// A target range must be published onto a range of the same length
+    fn check_ranges(&self) -> Result<(), String> {
+        match self.published_range {
+            Some(published)
+                if self.target_range.count() > 1
+                    && published.count() != self.target_range.count() =>
+            {
+                Err(format!(
+                    "Published ports {published} and target ports {} are ranges of different \
+                     lengths",
+                    self.target_range
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Convert to the long syntax, with one entry per target port
+    pub fn to_long(&self) -> Vec<PortOptions> {
+        let targets = self.target_range.ports();
+        let one_to_one = self.target_range.count() > 1
+            && self.published_range.map(|p| p.count()) == Some(self.target_range.count());
+        targets
+            .enumerate()
+            .map(|(i, target)| PortOptions {
+                target,
+                host_ip: self.host_ip.map(|ip| ip.to_string()),
+                published: self.published_range.map(|published| match one_to_one {
+                    true => (published.start + i as u16).to_string(),
+                    false => published.to_string(),
+                }),
+                protocol: Some(self.protocol),
+                mode: Some(Mode::Ingress),
+                extensions: Extensions::default(),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for PortMapping {
+    type Err = ValidationError;
+
+    fn from_str(port: &str) -> Result<Self, Self::Err> {
+        PortMapping::parse(port).map_err(|message| {
+            ValidationError::new(ErrorCode::InvalidPort, "", message).with_value(port)
+        })
+    }
+}
+
+/// Parse the long syntax. Its `mode` has no equivalent in the short syntax and is dropped.
+impl TryFrom<&PortOptions> for PortMapping {
+    type Error = ValidationError;
+
+    fn try_from(port: &PortOptions) -> Result<Self, Self::Error> {
+        let invalid = |message: String, value: String| {
+            ValidationError::new(ErrorCode::InvalidPort, "", message).with_value(value)
+        };
+        let host_ip = match port.host_ip.as_deref() {
+            None | Some("") => None,
+            Some(ip) => Some(
+                ip.trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid host IP {ip}"), ip.to_owned()))?,
+            ),
+        };
+        let published = port.published.as_deref().unwrap_or_default();
+        let published_range = match published {
+            "" => None,
+            published => Some(
+                PortRange::parse(published)
+                    .map_err(|message| invalid(message, published.to_owned()))?,
+            ),
+        };
+        let target = port.target.to_string();
+        let mapping = PortMapping {
+            host_ip,
+            published_range,
+            target_range: PortRange::parse(&target)
+                .map_err(|message| invalid(message, target.clone()))?,
+            protocol: port.protocol.unwrap_or(Protocol::Tcp),
+        };
+        mapping
+            .check_ranges()
+            .map_err(|message| invalid(message, published.to_owned()))?;
+        Ok(mapping)
+    }
+}
+
+/// The short syntax, leaving out the default `tcp` protocol
+impl Display for PortMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.host_ip {
+            Some(IpAddr::V6(ip)) => write!(f, "[{ip}]:")?,
+            Some(ip) => write!(f, "{ip}:")?,
+            None => (),
+        }
+        match self.published_range {
+            Some(published) => write!(f, "{published}:")?,
+            // Keep the empty published port, so the host IP is not read as one
+            None if self.host_ip.is_some() => write!(f, ":")?,
+            None => (),
+        }
+        write!(f, "{}", self.target_range)?;
+        match self.protocol {
+            Protocol::Tcp => Ok(()),
+            protocol => write!(f, "/{protocol}"),
+        }
+    }
+}
+
+/// A range of host ports published by a service
+struct Binding<'a> {
+    service: &'a str,
+    profiles: &'a [String],
+    path: String,
+    mapping: PortMapping,
+    published: PortRange,
+}
+
+impl Binding<'_> {
+    /// Whether both bindings claim a common host port
+    fn overlaps(&self, other: &Binding) -> bool {
+        let addresses = match (self.mapping.host_ip, other.mapping.host_ip) {
+            (None, _) | (_, None) => true,
+            (Some(a), Some(b)) => {
+                a == b || (a.is_ipv4() == b.is_ipv4() && (a.is_unspecified() || b.is_unspecified()))
+            }
+        };
+        addresses
+            && self.runs_with(other)
+            && self.mapping.protocol == other.mapping.protocol
+            && self.published.overlaps(&other.published)
+    }
+
+    /// Whether both services can be enabled at once, which services in profiles that have
+    /// none in common cannot
+    fn runs_with(&self, other: &Binding) -> bool {
+        self.profiles.is_empty()
+            || other.profiles.is_empty()
+            || self.profiles.iter().any(|p| other.profiles.contains(p))
+    }
+}
+
+impl Display for Binding<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.mapping.host_ip {
+            Some(IpAddr::V6(ip)) => write!(f, "[{ip}]:")?,
+            Some(ip) => write!(f, "{ip}:")?,
+            None => (),
+        }
+        write!(f, "{}/{}", self.published, self.mapping.protocol)
+    }
+}
+
+/// Report host ports published more than once on the same address and protocol, across all
+/// services, and fixed host ports published by services with several replicas
+///
+/// Services in profiles that have none in common are never enabled together, so they may
+/// publish the same host ports.
+pub(crate) fn validate_conflicts(compose: &Compose, errors: &mut ValidationErrors) {
+    let mut bindings: Vec<Binding> = Vec::new();
+    for (name, service) in &compose.services {
+        let Some(ports) = &service.ports else {
+            continue;
+        };
+        let path = pointer(&pointer("/services", name), "ports");
+        let replicas = service
+            .deploy
+            .as_ref()
+            .and_then(|d| d.replicas.map(usize::from))
+            .or(service.scale.map(|scale| scale as usize))
+            .unwrap_or(1);
+
+        for (i, mapping) in ports
+            .iter()
+            .enumerate()
+            .filter_map(|(i, port)| Some((i, port.mapping()?)))
+        {
+            // Ports without a published port get an ephemeral host port
+            let Some(published) = mapping.published_range else {
+                continue;
+            };
+            let binding = Binding {
+                service: name,
+                profiles: service.profiles.as_deref().unwrap_or_default(),
+                path: pointer(&path, i),
+                mapping,
+                published,
+            };
+
+            // Each replica needs its own host ports from the published range
+            let needed = replicas * binding.mapping.target_range.count();
+            if published.count() < needed {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::PortConflict,
+                        &binding.path,
+                        format!(
+                            "Host port {binding} cannot be published by {replicas} replicas, \
+                             use a range of at least {needed} ports or let Compose pick one"
+                        ),
+                    )
+                    .with_value(published.to_string()),
+                );
+            }
+
+            for other in bindings.iter().filter(|other| binding.overlaps(other)) {
+                errors.add_error(
+                    ValidationError::new(
+                        ErrorCode::PortConflict,
+                        &binding.path,
+                        format!(
+                            "Host port {binding} is already published by service {} as \
+                             {other} at {}",
+                            other.service, other.path
+                        ),
+                    )
+                    .with_value(published.to_string()),
+                );
+            }
+            bindings.push(binding);
+        }
+    }
+}
+
+impl Normalize for Vec<Ports> {
+    fn normalize(&mut self) {
+        *self = std::mem::take(self)
+            .into_iter()
+            .flat_map(|port| match port {
+                Ports::Short(short) => match PortMapping::parse(&short) {
+                    Ok(mapping) => mapping.to_long().into_iter().map(Ports::Long).collect(),
+                    // Ports that cannot be parsed are left as they are
+                    Err(_) => vec![Ports::Short(short)],
+                },
+                Ports::Long(mut long) => {
+                    fill_defaults(&mut long);
+                    vec![Ports::Long(long)]
+                }
+            })
+            .collect();
+    }
+}
+
+fn fill_defaults(port: &mut PortOptions) {
+    port.protocol.get_or_insert(Protocol::Tcp);
+    port.mode.get_or_insert(Mode::Ingress);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_syntax() {
+        let long = |port: &str| port.parse::<PortMapping>().ok().map(|m| m.to_long());
+        let port = &long("127.0.0.1:8080:80/udp").unwrap()[0];
+        assert_eq!(port.target, 80);
+        assert_eq!(port.host_ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(port.published.as_deref(), Some("8080"));
+        assert_eq!(port.protocol, Some(Protocol::Udp));
+
+        let ports = long("9090-9091:8080-8081").unwrap();
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[1].target, 8081);
+        assert_eq!(ports[1].published.as_deref(), Some("9091"));
+
+        let port = &long("[::1]:6001:6001").unwrap()[0];
+        assert_eq!(port.host_ip.as_deref(), Some("::1"));
+
+        assert_eq!(
+            long("9000-9005:80").unwrap()[0].published.as_deref(),
+            Some("9000-9005")
+        );
+        assert!(long("3000").unwrap()[0].published.is_none());
+        assert!(long("http").is_none());
+    }
+
+    #[test]
+    fn test_port_mapping() {
+        let mapping: PortMapping = "127.0.0.1:8080-8081:80-81/udp".parse().unwrap();
+        assert_eq!(
+            mapping,
+            PortMapping {
+                host_ip: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                published_range: PortRange::new(8080, 8081),
+                target_range: PortRange::new(80, 81).unwrap(),
+                protocol: Protocol::Udp,
+            }
+        );
+        assert_eq!(mapping.to_string(), "127.0.0.1:8080-8081:80-81/udp");
+
+        let mapping: PortMapping = "[::1]:80:80".parse().unwrap();
+        assert_eq!(mapping.host_ip, Some(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+        assert_eq!(mapping.to_string(), "[::1]:80:80");
+
+        // An empty published port is picked by Compose on the host IP
+        for port in ["127.0.0.1::80", "[::1]::80"] {
+            let mapping: PortMapping = port.parse().unwrap();
+            assert!(mapping.host_ip.is_some());
+            assert_eq!(mapping.published_range, None);
+            assert_eq!(mapping.to_string(), port);
+        }
+
+        // The long syntax converts back to the same mapping
+        for port in [
+            "3000",
+            "8080:80",
+            "[::1]:80:80/sctp",
+            "9000-9005:80",
+            "127.0.0.1::80",
+        ] {
+            let mapping: PortMapping = port.parse().unwrap();
+            let long = mapping.to_long();
+            assert_eq!(PortMapping::try_from(&long[0]).unwrap(), mapping);
+            assert_eq!(mapping.to_string(), port);
+        }
+
+        let error = |port: &str| port.parse::<PortMapping>().unwrap_err().message;
+        assert_eq!(
+            error("8080-8082:80-81"),
+            "Published ports 8080-8082 and target ports 80-81 are ranges of different lengths"
+        );
+        assert_eq!(
+            error("8080:80-81"),
+            "Published ports 8080 and target ports 80-81 are ranges of different lengths"
+        );
+        assert_eq!(
+            error("70000:80"),
+            "Port 70000 is out of range, expected 1 to 65535"
+        );
+        assert_eq!(error("0:80"), "Port 0 is out of range, expected 1 to 65535");
+        assert_eq!(
+            error("81-80"),
+            "Invalid port range 81-80, it ends before it starts"
+        );
+        assert_eq!(
+            error("80/http"),
+            "Invalid protocol http, expected tcp, udp or sctp"
+        );
+        assert_eq!(error("localhost:80:80"), "Invalid host IP localhost");
+        assert_eq!(
+            error("::1:80:80"),
+            "Invalid port, an IPv6 host IP must be in brackets, as in [::1]:80:80"
+        );
+        assert_eq!(error("[::1:80:80"), "Invalid host IP in [::1:80:80");
+
+        // Errors in the long syntax carry the value of the field at fault
+        let long = |host_ip: &str, published: &str, target: u16| PortOptions {
+            target,
+            host_ip: Some(host_ip.to_owned()),
+            published: Some(published.to_owned()),
+            protocol: None,
+            mode: None,
+            extensions: Extensions::default(),
+        };
+        let value = |port: PortOptions| PortMapping::try_from(&port).unwrap_err().value;
+        assert_eq!(
+            value(long("localhost", "8080", 80)).as_deref(),
+            Some("localhost")
+        );
+        assert_eq!(
+            value(long("127.0.0.1", "http", 80)).as_deref(),
+            Some("http")
+        );
+        assert_eq!(value(long("127.0.0.1", "8080", 0)).as_deref(), Some("0"));
+
+        assert_eq!(PortRange::new(81, 80), None);
+        assert_eq!(PortRange::new(0, 80), None);
+        let range = PortRange::new(65534, 65535).unwrap();
+        assert_eq!(
+            (range.start(), range.end(), range.count()),
+            (65534, 65535, 2)
+        );
+
+        // Ranges of different lengths built by hand are published as a whole
+        let mapping = PortMapping {
+            host_ip: None,
+            published_range: PortRange::new(65535, 65535),
+            target_range: PortRange::new(80, 81).unwrap(),
+            protocol: Protocol::Tcp,
+        };
+        let published: Vec<_> = mapping.to_long().into_iter().map(|p| p.published).collect();
+        assert_eq!(
+            published,
+            [Some("65535".to_owned()), Some("65535".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_integer_ports() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - 3000
+              - "8080:80"
+              - target: 443
+                published: 8443
+          api:
+            image: api
+            ports:
+              - target: 80
+                published: 8443
+              - 70000
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let reported: Vec<_> = errors
+            .all_errors()
+            .iter()
+            .map(|e| (e.code, e.path.as_str(), e.value.as_deref()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    ErrorCode::InvalidPort,
+                    "/services/api/ports/1",
+                    Some("70000")
+                ),
+                (
+                    ErrorCode::PortConflict,
+                    "/services/api/ports/0",
+                    Some("8443")
+                ),
+            ]
+        );
+
+        // Short and long entries can be mixed, and numbers are kept as their string form
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - 3000
+              - target: 443
+                published: 8443
+        "#;
+        let web = &Compose::new(yaml).unwrap().services["web"];
+        let ports = web.ports.as_ref().unwrap();
+        assert!(matches!(&ports[0], Ports::Short(port) if port == "3000"));
+        assert!(
+            matches!(&ports[1], Ports::Long(port) if port.published.as_deref() == Some("8443"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_ports() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - "8080:80"
+              - "8081-8083:80-81"
+              - "${WEB_PORT:-8080}:80"
+          api:
+            image: api
+            ports:
+              - target: 80
+                published: "0"
+              - target: 80
+                host_ip: ${HOST_IP}
+                published: "8080"
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let reported: Vec<_> = errors
+            .with_code(ErrorCode::InvalidPort)
+            .map(|e| (e.path.as_str(), e.value.as_deref()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("/services/web/ports/1", Some("8081-8083:80-81")),
+                ("/services/api/ports/0", Some("0")),
+            ]
+        );
+
+        // Entries with variables are not interpolated, so they are not checked for conflicts
+        assert_eq!(errors.with_code(ErrorCode::PortConflict).count(), 0);
+    }
+
+    #[test]
+    fn test_port_conflicts() {
+        let yaml = r#"
+        services:
+          web:
+            image: web
+            ports:
+              - "8080:80"
+              - "127.0.0.1:5000:5000"
+              - "[::1]:6000:6000"
+              - "9000-9005:9000"
+              - "53:53/udp"
+          api:
+            image: api
+            ports:
+              - "8080:8080"
+              - "0.0.0.0:5000:5000"
+              - "[::]:6000:6000"
+              - "127.0.0.1:9003:9003"
+              - "53:53"
+              - "3000"
+          admin:
+            image: admin
+            ports:
+              - target: 80
+                host_ip: 127.0.0.2
+                published: "5000"
+              - target: 80
+                host_ip: "::"
+                published: "7000"
+              - target: 80
+                host_ip: 127.0.0.1
+                published: "7000"
+          worker:
+            image: worker
+            deploy:
+              replicas: 3
+            ports:
+              - "4000:4000"
+              - "4100-4102:4000"
+          cluster:
+            image: cluster
+            deploy:
+              replicas: 2
+            ports:
+              - "8500-8501:8500-8501"
+          batch:
+            image: batch
+            ports:
+              - "8000-8002:8000-8002"
+              - "8001-8002:9001-9002"
+          dev:
+            image: app
+            profiles: [dev]
+            ports:
+              - "8088:80"
+          prod:
+            image: app
+            profiles: [prod]
+            ports:
+              - "8088:80"
+          debug:
+            image: app
+            profiles: [debug, dev]
+            ports:
+              - "8088:80"
+        "#;
+
+        let errors = Compose::new(yaml).unwrap_err();
+        let reported: Vec<_> = errors
+            .with_code(ErrorCode::PortConflict)
+            .map(|e| (e.path.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "/services/api/ports/0",
+                    "Host port 8080/tcp is already published by service web as 8080/tcp at \
+                     /services/web/ports/0"
+                ),
+                (
+                    "/services/api/ports/1",
+                    "Host port 0.0.0.0:5000/tcp is already published by service web as \
+                     127.0.0.1:5000/tcp at /services/web/ports/1"
+                ),
+                (
+                    "/services/api/ports/2",
+                    "Host port [::]:6000/tcp is already published by service web as \
+                     [::1]:6000/tcp at /services/web/ports/2"
+                ),
+                (
+                    "/services/api/ports/3",
+                    "Host port 127.0.0.1:9003/tcp is already published by service web as \
+                     9000-9005/tcp at /services/web/ports/3"
+                ),
+                (
+                    "/services/admin/ports/0",
+                    "Host port 127.0.0.2:5000/tcp is already published by service api as \
+                     0.0.0.0:5000/tcp at /services/api/ports/1"
+                ),
+                (
+                    "/services/worker/ports/0",
+                    "Host port 4000/tcp cannot be published by 3 replicas, use a range of at \
+                     least 3 ports or let Compose pick one"
+                ),
+                (
+                    "/services/cluster/ports/0",
+                    "Host port 8500-8501/tcp cannot be published by 2 replicas, use a range of \
+                     at least 4 ports or let Compose pick one"
+                ),
+                (
+                    "/services/batch/ports/1",
+                    "Host port 8001-8002/tcp is already published by service batch as \
+                     8000-8002/tcp at /services/batch/ports/0"
+                ),
+                (
+                    "/services/debug/ports/0",
+                    "Host port 8088/tcp is already published by service dev as 8088/tcp at \
+                     /services/dev/ports/0"
+                ),
+            ]
+        );
     }
 }
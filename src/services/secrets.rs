@@ -1,8 +1,19 @@
 use serde::{Deserialize, Serialize};
 
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Normalize, Validate},
+    errors::{ErrorCode, ValidationError},
+    extensions::Extensions,
+    span::pointer,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "ServiceSecret"),
+    ts(rename = "ServiceSecret")
+)]
 #[serde(untagged)]
 pub enum Secret {
     Short(String),
@@ -10,40 +21,86 @@ pub enum Secret {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct SecretOptions {
     pub source: String,
-    pub target: String,
-    pub uid: String,
-    pub gid: String,
-    pub mode: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 impl Validate for Secret {
-    fn validate(&self, ctx: &Compose, errors: &mut crate::errors::ValidationErrors) {
+    fn validate(&self, ctx: &Compose, path: &str, errors: &mut crate::errors::ValidationErrors) {
+        let Some(available_secrets) = &ctx.secrets else {
+            return;
+        };
         match self {
             Secret::Short(s) => {
-                ctx.secrets.as_ref().map(|available_secrets| {
-                    if !available_secrets.contains_key(s) {
-                        errors.add_error(crate::errors::ValidationError::InvalidValue(format!(
-                            "Service secrets reference an unknown secret: {s}"
-                        )));
-                    }
-                });
+                if !available_secrets.contains_key(s) {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::UnknownSecret,
+                            path,
+                            format!("Service secrets reference an unknown secret: {s}"),
+                        )
+                        .with_value(s),
+                    );
+                }
             }
             Secret::Long(s) => {
-                ctx.secrets.as_ref().map(|available_secrets| {
-                    if !available_secrets.contains_key(s.source.as_str()) {
-                        errors.add_error(crate::errors::ValidationError::InvalidValue(format!(
-                            "Service secrets reference an unknown secret: {}",
-                            s.source
-                        )));
-                    }
-                });
+                if !available_secrets.contains_key(s.source.as_str()) {
+                    errors.add_error(
+                        ValidationError::new(
+                            ErrorCode::UnknownSecret,
+                            &pointer(path, "source"),
+                            format!("Service secrets reference an unknown secret: {}", s.source),
+                        )
+                        .with_value(&s.source),
+                    );
+                }
             }
         }
     }
 }
 
+impl Normalize for Secret {
+    fn normalize(&mut self) {
+        let long = match self {
+            Secret::Short(source) => SecretOptions {
+                source: source.clone(),
+                target: None,
+                uid: None,
+                gid: None,
+                mode: None,
+                extensions: Extensions::default(),
+            },
+            Secret::Long(long) => long.clone(),
+        };
+        let target = long
+            .target
+            .clone()
+            .unwrap_or_else(|| format!("/run/secrets/{}", long.source));
+        *self = Secret::Long(SecretOptions {
+            target: Some(target),
+            ..long
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
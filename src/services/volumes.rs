@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 
-use crate::compose::{Compose, Validate};
+use crate::{
+    compose::{Compose, Normalize, Validate},
+    extensions::Extensions,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(untagged)]
 pub enum Volumes {
     String(String),
@@ -11,6 +15,7 @@ pub enum Volumes {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct ShortVolumeOptions {
     pub volume: String,
     pub container_path: String,
@@ -18,10 +23,16 @@ pub struct ShortVolumeOptions {
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct LongVolumeOptions {
     #[serde(rename = "type")]
     pub volume_type: VolumeType,
-    pub source: String,
+
+    /// Not set for anonymous volumes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
     pub target: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -38,9 +49,14 @@ pub struct LongVolumeOptions {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub consistency: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum VolumeType {
     Volume,
@@ -50,31 +66,61 @@ pub enum VolumeType {
     Cluster,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Bind {
-    pub propagation: String,
-    pub create_host_path: String,
-    pub selinux: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub propagation: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub create_host_path: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selinux: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct VolumeOptions {
     pub nocopy: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(
+    feature = "typegen",
+    schemars(rename = "VolumeTmpfs"),
+    ts(rename = "VolumeTmpfs")
+)]
 pub struct Tmpfs {
     pub size: String,
     pub mode: String,
 }
 
+impl Volumes {
+    /// The host path of a bind mount, in any syntax
+    pub fn bind_source(&self) -> Option<String> {
+        let mut long = self.clone();
+        long.normalize();
+        match long {
+            Volumes::Long(LongVolumeOptions {
+                volume_type: VolumeType::Bind,
+                source,
+                ..
+            }) => source,
+            _ => None,
+        }
+    }
+}
+
 impl Validate for Volumes {
     /// Note: Currently, this implementation of volumes always assumes references to volumes will
     /// be named volumes from the top level volumes section. However, the official docs say that
     /// the services:volumes should also allow for path references. Since this library does not
     /// care about the host system, maybe there should be additional options to check for host
     /// references.
-    fn validate(&self, _ctx: &Compose, _errors: &mut crate::errors::ValidationErrors) {
+    fn validate(&self, _ctx: &Compose, _path: &str, _errors: &mut crate::errors::ValidationErrors) {
         // match self {
         //     Volumes::String(s) => {
         //         ctx.volumes.as_ref().map(|available_volumes| {
@@ -106,10 +152,74 @@ impl Validate for Volumes {
         //         });
         //     }
         // }
-        ()
     }
 }
 
+impl Normalize for Volumes {
+    fn normalize(&mut self) {
+        let long = match self {
+            Volumes::String(volume) => long_syntax(volume),
+            Volumes::Short(short) => long_syntax(&format!(
+                "{}:{}:{}",
+                short.volume, short.container_path, short.access_mode
+            )),
+            Volumes::Long(_) => None,
+        };
+        if let Some(long) = long {
+            *self = Volumes::Long(long);
+        }
+    }
+}
+
+/// Convert the short syntax `[SOURCE:]TARGET[:MODE]`, where a source that looks like a path is
+/// a bind mount and any other source is a named volume
+fn long_syntax(volume: &str) -> Option<LongVolumeOptions> {
+    let parts: Vec<&str> = volume.split(':').collect();
+    let (source, target, mode) = match parts.as_slice() {
+        [target] => (None, *target, ""),
+        [source, target] => (Some(*source), *target, ""),
+        [source, target, mode] => (Some(*source), *target, *mode),
+        _ => return None,
+    };
+
+    let is_path = source.is_some_and(|s| s.starts_with(['/', '.', '~']));
+    let mut long = LongVolumeOptions {
+        volume_type: if is_path {
+            VolumeType::Bind
+        } else {
+            VolumeType::Volume
+        },
+        source: source.map(str::to_owned),
+        target: target.to_owned(),
+        read_only: None,
+        bind: is_path.then(|| Bind {
+            create_host_path: Some(true),
+            ..Bind::default()
+        }),
+        volume: None,
+        tmpfs: None,
+        consistency: None,
+        extensions: Extensions::default(),
+    };
+
+    for option in mode.split(',').filter(|o| !o.is_empty()) {
+        match option {
+            "ro" => long.read_only = Some(true),
+            "rw" => (),
+            "nocopy" => long.volume = Some(VolumeOptions { nocopy: true }),
+            "z" | "Z" => {
+                long.bind.get_or_insert_with(Bind::default).selinux = Some(option.to_owned())
+            }
+            "shared" | "rshared" | "slave" | "rslave" | "private" | "rprivate" => {
+                long.bind.get_or_insert_with(Bind::default).propagation = Some(option.to_owned())
+            }
+            "cached" | "delegated" | "consistent" => long.consistency = Some(option.to_owned()),
+            _ => return None,
+        }
+    }
+    Some(long)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,485 @@
+//! Source locations for the nodes of a Compose manifest
+
+use std::collections::HashMap;
+
+use saphyr_parser::{Event, Parser, ScalarStyle};
+
+/// A position in the source document
+///
+/// `line` and `column` are 1-indexed, `offset` is the 0-indexed byte offset into the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl From<serde_yaml::Location> for Location {
+    fn from(location: serde_yaml::Location) -> Self {
+        Location {
+            line: location.line(),
+            column: location.column(),
+            offset: location.index(),
+        }
+    }
+}
+
+/// The range of the source document covered by a YAML node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+
+    /// Index of the document the span is in, when several documents were merged
+    pub document: usize,
+}
+
+impl Span {
+    /// Create an empty span at a single location
+    pub fn point(location: Location) -> Self {
+        Span {
+            start: location,
+            end: location,
+            document: 0,
+        }
+    }
+
+    /// Move the span into the document at index `document`
+    pub(crate) fn in_document(mut self, document: usize) -> Self {
+        self.document = document;
+        self
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.start.line, self.start.column)
+    }
+}
+
+/// Append a token to a JSON pointer style path, escaping it as needed
+pub fn pointer(path: &str, token: impl std::fmt::Display) -> String {
+    let token = token.to_string().replace('~', "~0").replace('/', "~1");
+    format!("{path}/{token}")
+}
+
+/// Split a JSON pointer style path into its unescaped tokens
+pub(crate) fn tokens(path: &str) -> Vec<String> {
+    path.split('/')
+        .skip(1)
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// The string form of a mapping key or scalar, as used in paths
+pub(crate) fn key_token(key: &serde_yaml::Value) -> String {
+    match key {
+        serde_yaml::Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim_end()
+            .to_owned(),
+    }
+}
+
+/// Spans of every node in a document, keyed by their JSON pointer style path
+///
+/// A source map may cover several documents that were merged in order, in which case later
+/// documents take precedence when looking up a path.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SourceMap {
+    documents: Vec<HashMap<String, Node>>,
+    /// The path of the anchored node that each alias refers to, for the last document
+    aliases: HashMap<String, String>,
+}
+
+/// A node of the document as it is written in the source
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Node {
+    pub(crate) span: Span,
+    /// Span of the key, for values of a mapping
+    pub(crate) key: Option<Span>,
+    pub(crate) kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeKind {
+    /// A scalar or an alias. Quoted scalars keep their quote character.
+    Scalar { quote: Option<char> },
+    /// A mapping or a sequence, which is either in block style or in flow style
+    Collection { flow: bool },
+}
+
+enum Frame {
+    Mapping {
+        path: String,
+        start: Location,
+        /// The pending key, along with its span, until its value is seen
+        key: Option<(String, Option<Span>)>,
+        flow: bool,
+    },
+    Sequence {
+        path: String,
+        start: Location,
+        index: usize,
+        flow: bool,
+    },
+}
+
+impl SourceMap {
+    /// Build the source map for `contents`, which is the document at index `document`
+    ///
+    /// Parsing stops silently at the first scan error, so a partially valid document still
+    /// yields spans for everything up to the error.
+    pub(crate) fn for_document(contents: &str, document: usize) -> Self {
+        let (nodes, aliases) = Self::parse(contents, document);
+        SourceMap {
+            documents: vec![nodes],
+            aliases,
+        }
+    }
+
+    /// Combine the source maps of documents that are merged in order
+    pub(crate) fn layered(maps: Vec<SourceMap>) -> Self {
+        let mut layered = SourceMap::default();
+        for map in maps {
+            layered.documents.extend(map.documents);
+            layered.aliases = map.aliases;
+        }
+        layered
+    }
+
+    fn parse(contents: &str, document: usize) -> (HashMap<String, Node>, HashMap<String, String>) {
+        let offsets = ByteOffsets::new(contents);
+        let mut nodes = HashMap::new();
+        let mut anchors: HashMap<usize, String> = HashMap::new();
+        let mut aliases = HashMap::new();
+        let mut stack: Vec<Frame> = Vec::new();
+        let span = |start, end| Span {
+            start,
+            end,
+            document,
+        };
+
+        for result in Parser::new_from_str(contents) {
+            let Ok((event, marker)) = result else {
+                break;
+            };
+            let start = offsets.location(marker.start.index());
+            let end = offsets.location(marker.end.index());
+            let flow = matches!(contents.get(start.offset..), Some(s) if s.starts_with(['{', '[']));
+
+            match event {
+                Event::Scalar(value, style, anchor, _) => {
+                    let quote = match style {
+                        ScalarStyle::SingleQuoted => Some('\''),
+                        ScalarStyle::DoubleQuoted => Some('"'),
+                        _ => None,
+                    };
+                    let end = match quote {
+                        Some(_) => quoted_end(contents, start, &offsets).unwrap_or(end),
+                        None => end,
+                    };
+                    if let Some((path, key)) =
+                        Self::node_path(&mut stack, Some(&value), span(start, end))
+                    {
+                        if anchor != 0 {
+                            anchors.insert(anchor, path.clone());
+                        }
+                        nodes.insert(
+                            path,
+                            Node {
+                                span: span(start, end),
+                                key,
+                                kind: NodeKind::Scalar { quote },
+                            },
+                        );
+                    }
+                }
+                Event::Alias(anchor) => {
+                    if let Some((path, key)) = Self::node_path(&mut stack, None, span(start, end)) {
+                        if let Some(target) = anchors.get(&anchor) {
+                            aliases.insert(path.clone(), target.clone());
+                        }
+                        nodes.insert(
+                            path,
+                            Node {
+                                span: span(start, end),
+                                key,
+                                kind: NodeKind::Scalar { quote: None },
+                            },
+                        );
+                    }
+                }
+                Event::MappingStart(anchor, _) => {
+                    let (path, key) =
+                        Self::node_path(&mut stack, None, span(start, end)).unwrap_or_default();
+                    if anchor != 0 {
+                        anchors.insert(anchor, path.clone());
+                    }
+                    if let Some(key) = key {
+                        nodes.insert(
+                            path.clone(),
+                            Node {
+                                span: span(start, start),
+                                key: Some(key),
+                                kind: NodeKind::Collection { flow },
+                            },
+                        );
+                    }
+                    stack.push(Frame::Mapping {
+                        path,
+                        start,
+                        key: None,
+                        flow,
+                    });
+                }
+                Event::SequenceStart(anchor, _) => {
+                    let (path, key) =
+                        Self::node_path(&mut stack, None, span(start, end)).unwrap_or_default();
+                    if anchor != 0 {
+                        anchors.insert(anchor, path.clone());
+                    }
+                    if let Some(key) = key {
+                        nodes.insert(
+                            path.clone(),
+                            Node {
+                                span: span(start, start),
+                                key: Some(key),
+                                kind: NodeKind::Collection { flow },
+                            },
+                        );
+                    }
+                    stack.push(Frame::Sequence {
+                        path,
+                        start,
+                        index: 0,
+                        flow,
+                    });
+                }
+                Event::MappingEnd | Event::SequenceEnd => {
+                    if let Some(
+                        Frame::Mapping {
+                            path,
+                            start: s,
+                            flow,
+                            ..
+                        }
+                        | Frame::Sequence {
+                            path,
+                            start: s,
+                            flow,
+                            ..
+                        },
+                    ) = stack.pop()
+                    {
+                        let key = nodes.get(&path).and_then(|n: &Node| n.key);
+                        nodes.insert(
+                            path,
+                            Node {
+                                span: span(s, end),
+                                key,
+                                kind: NodeKind::Collection { flow },
+                            },
+                        );
+                    }
+                }
+                Event::DocumentEnd => break,
+                _ => (),
+            }
+        }
+
+        (nodes, aliases)
+    }
+
+    /// Resolve the path of the node that starts with the current event, along with the span of
+    /// its key if it is the value of a mapping
+    ///
+    /// Returns `None` when the node is a mapping key, in which case the key is remembered so
+    /// that the following value node is placed beneath it.
+    fn node_path(
+        stack: &mut [Frame],
+        scalar: Option<&str>,
+        span: Span,
+    ) -> Option<(String, Option<Span>)> {
+        match stack.last_mut() {
+            None => Some((String::new(), None)),
+            Some(Frame::Sequence { path, index, .. }) => {
+                let node = pointer(path, *index);
+                *index += 1;
+                Some((node, None))
+            }
+            Some(Frame::Mapping { path, key, .. }) => match key.take() {
+                Some((k, key_span)) => Some((pointer(path, k), key_span)),
+                None => {
+                    // Complex keys are not addressable, but still consume the key slot
+                    *key = Some((scalar.unwrap_or_default().to_owned(), scalar.map(|_| span)));
+                    None
+                }
+            },
+        }
+    }
+
+    /// Find the span for `path`, falling back to its nearest ancestor that has one
+    pub(crate) fn get(&self, path: &str) -> Option<Span> {
+        let mut current = path;
+        loop {
+            if let Some(node) = self.documents.iter().rev().find_map(|d| d.get(current)) {
+                return Some(node.span);
+            }
+            match current.rfind('/') {
+                Some(i) => current = &current[..i],
+                None => return None,
+            }
+        }
+    }
+
+    /// The node at exactly `path` in the last document
+    pub(crate) fn node(&self, path: &str) -> Option<&Node> {
+        self.documents.last()?.get(path)
+    }
+
+    /// The node that the value at `path` of the last document was written as
+    ///
+    /// Unlike [`SourceMap::node`], this follows aliases and `<<` merge keys to the anchored
+    /// node the value comes from.
+    pub(crate) fn origin(&self, path: &str) -> Option<&Node> {
+        self.node(&self.origin_path(path)?)
+    }
+
+    fn origin_path(&self, path: &str) -> Option<String> {
+        if let Some(target) = self.aliases.get(path) {
+            return self.origin_path(target);
+        }
+        if self.node(path).is_some() {
+            return Some(path.to_owned());
+        }
+
+        let (parent, token) = path.rsplit_once('/')?;
+        let parent = self.origin_path(parent)?;
+        let child = format!("{parent}/{token}");
+        if self.node(&child).is_some() || self.aliases.contains_key(&child) {
+            return self.origin_path(&child);
+        }
+        if token == "<<" {
+            return None;
+        }
+
+        // The key may come from a merge key, which is either a single mapping or a list of them
+        let merge = pointer(&parent, "<<");
+        let within = |source: String| self.origin_path(&format!("{source}/{token}"));
+        if let Some(origin) = self.origin_path(&merge).and_then(within) {
+            return Some(origin);
+        }
+        for index in 0.. {
+            let source = self.origin_path(&pointer(&merge, index))?;
+            if let Some(origin) = within(source) {
+                return Some(origin);
+            }
+        }
+        None
+    }
+}
+
+/// Translates the parser's character indices into byte offsets, lines and columns
+struct ByteOffsets {
+    chars: Vec<(usize, usize, usize)>,
+    len: usize,
+}
+
+impl ByteOffsets {
+    fn new(contents: &str) -> Self {
+        let mut chars = Vec::with_capacity(contents.len());
+        let (mut line, mut column) = (1, 1);
+        for (offset, c) in contents.char_indices() {
+            chars.push((offset, line, column));
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        chars.push((contents.len(), line, column));
+        ByteOffsets {
+            len: chars.len(),
+            chars,
+        }
+    }
+
+    fn location(&self, index: usize) -> Location {
+        let (offset, line, column) = self.chars[index.min(self.len - 1)];
+        Location {
+            line,
+            column,
+            offset,
+        }
+    }
+
+    fn at_offset(&self, offset: usize) -> Location {
+        let index = self
+            .chars
+            .binary_search_by_key(&offset, |(o, _, _)| *o)
+            .unwrap_or_else(|i| i);
+        self.location(index)
+    }
+}
+
+/// Find the end of a quoted scalar that begins at `start`
+///
+/// The parser reports the end of quoted scalars imprecisely, so scan for the closing quote.
+fn quoted_end(contents: &str, start: Location, offsets: &ByteOffsets) -> Option<Location> {
+    let rest = contents.get(start.offset..)?;
+    let quote = rest.chars().next()?;
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            c if c == quote => {
+                if quote == '\'' && rest[i + 1..].starts_with('\'') {
+                    chars.next();
+                    continue;
+                }
+                return Some(offsets.at_offset(start.offset + i + 1));
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_map_spans() {
+        let yaml = "services:\n  web:\n    image: \"nginx\" # comment\n    expose:\n      - 80\n      - 443\n";
+        let source = SourceMap::for_document(yaml, 0);
+
+        let image = source.get("/services/web/image").unwrap();
+        assert_eq!((image.start.line, image.start.column), (3, 12));
+        assert_eq!(&yaml[image.start.offset..image.end.offset], "\"nginx\"");
+
+        let port = source.get("/services/web/expose/1").unwrap();
+        assert_eq!((port.start.line, port.start.column), (6, 9));
+        assert_eq!(&yaml[port.start.offset..port.end.offset], "443");
+    }
+
+    #[test]
+    fn test_source_map_falls_back_to_ancestor() {
+        let yaml = "services:\n  web:\n    image: nginx\n";
+        let source = SourceMap::for_document(yaml, 0);
+
+        let missing = source.get("/services/web/container_name").unwrap();
+        assert_eq!(missing, source.get("/services/web").unwrap());
+        assert_eq!(missing.start.line, 3);
+    }
+
+    #[test]
+    fn test_pointer_escaping() {
+        assert_eq!(pointer("/services", "a/b~c"), "/services/a~1b~0c");
+    }
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the unit tests
+
+/// Compare `contents` with the checked in file at `path`, rewriting it if asked to
+///
+/// Set `UPDATE_GENERATED` when running the tests to rewrite every golden file.
+pub(crate) fn check(path: &str, contents: &str) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    if std::env::var_os("UPDATE_GENERATED").is_some() {
+        std::fs::write(&path, contents).unwrap();
+    }
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        existing == contents,
+        "{} is out of date, run the tests with UPDATE_GENERATED=1",
+        path.display()
+    );
+}
@@ -0,0 +1,135 @@
+//! JSON Schema and TypeScript definitions generated from the model
+//!
+//! Enabled by the `typegen` feature. The output is checked in under `generated/`, so that
+//! frontends can use it without building the crate, and a test fails when it is out of date.
+//! Run the tests with `UPDATE_GENERATED=1` to regenerate it.
+//!
+//! Types whose names are used in several modules are renamed after their module, e.g.
+//! [`services::build::Labels`] becomes `BuildLabels`.
+
+use ts_rs::{Config, TS};
+
+use crate::*;
+
+/// List the TypeScript declarations of every public type of the model
+macro_rules! declarations {
+    ($cfg:expr, $($ty:ty,)*) => {
+        vec![$(<$ty as TS>::decl($cfg),)*]
+    };
+}
+
+/// The JSON Schema of a [`Compose`] manifest
+pub fn json_schema() -> String {
+    let schema = schemars::schema_for!(Compose);
+    let mut schema = serde_json::to_string_pretty(&schema).expect("schemas serialize to JSON");
+    schema.push('\n');
+    schema
+}
+
+/// TypeScript declarations for every public type of the model
+pub fn typescript() -> String {
+    // Integers are plain numbers in JSON
+    let cfg = Config::new().with_large_int("number");
+    let declarations = declarations!(
+        &cfg,
+        compose::Compose,
+        services::Service,
+        services::Labels,
+        services::Tmpfs,
+        services::Config,
+        services::ConfigDetails,
+        services::Cgroup,
+        services::Command,
+        services::CredentialSpec,
+        services::DependsOn,
+        services::DependsOnDetail,
+        services::DependsOnCondition,
+        services::Extends,
+        services::ExtendsDetails,
+        services::PullPolicy,
+        services::Restart,
+        services::Ulimits,
+        services::Nofile,
+        services::Capabilities,
+        services::logging::Logging,
+        services::logging::Options,
+        services::ports::Ports,
+        services::ports::PortOptions,
+        services::ports::Protocol,
+        services::ports::Mode,
+        services::deploy::Deploy,
+        services::deploy::Placement,
+        services::deploy::EndpointMode,
+        services::deploy::Mode,
+        services::deploy::Resources,
+        services::deploy::Limits,
+        services::deploy::DriverCapabilities,
+        services::deploy::RestartPolicy,
+        services::deploy::RestartCondition,
+        services::deploy::Reservations,
+        services::deploy::RollbackConfig,
+        services::deploy::UpdateConfig,
+        services::deploy::Order,
+        services::deploy::FailureAction,
+        services::blkio_config::BlkioConfig,
+        services::blkio_config::WeightDevice,
+        services::blkio_config::DeviceReadBps,
+        services::blkio_config::DeviceWriteBps,
+        services::blkio_config::DeviceReadIops,
+        services::blkio_config::DeviceWriteIops,
+        services::blkio_config::Rate,
+        services::build::Build,
+        services::build::BuildDetails,
+        services::build::BuildArgs,
+        services::build::SshArgs,
+        services::build::AdditionalContexts,
+        services::build::Labels,
+        services::build::ShmSize,
+        services::build::BuildSecret,
+        services::build::SecretDetails,
+        services::volumes::Volumes,
+        services::volumes::ShortVolumeOptions,
+        services::volumes::LongVolumeOptions,
+        services::volumes::VolumeType,
+        services::volumes::Bind,
+        services::volumes::VolumeOptions,
+        services::volumes::Tmpfs,
+        services::healthcheck::HealthCheck,
+        services::healthcheck::Test,
+        services::secrets::Secret,
+        services::secrets::SecretOptions,
+        services::networks::Networks,
+        services::networks::NetworkOptions,
+        networks::Network,
+        networks::Ipam,
+        networks::Driver,
+        networks::Config,
+        volumes::Volume,
+        volumes::DriverOpts,
+        configs::Config,
+        secrets::Secret,
+        include::Include,
+        include::IncludeDetails,
+        include::Paths,
+    );
+
+    let mut typescript = String::from("// Generated by compose-validatr, do not edit\n");
+    for declaration in declarations {
+        typescript.push_str("\nexport ");
+        typescript.push_str(&declaration);
+        typescript.push('\n');
+    }
+    typescript
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::check;
+
+    #[test]
+    fn test_generated_files_are_up_to_date() {
+        check("generated/compose.schema.json", &json_schema());
+        check("generated/compose.ts", &typescript());
+    }
+}
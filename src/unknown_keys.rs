@@ -0,0 +1,353 @@
+//! Reporting of unknown keys
+//!
+//! Keys that are not part of the model, such as a misspelled `enviroment`, are dropped during
+//! deserialization without a trace. With [`ComposeBuilder::unknown_keys`] they are reported
+//! instead, along with the nearest field of the element they appear in.
+//!
+//! Unknown keys are found by comparing the document with the deserialized manifest serialized
+//! back, which also covers elements with several syntaxes. `x-` extension fields are never
+//! reported.
+//!
+//! [`ComposeBuilder::unknown_keys`]: crate::compose::ComposeBuilder::unknown_keys
+
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde_yaml::Value;
+
+use crate::{
+    configs,
+    errors::{ErrorCode, Severity, ValidationError, ValidationErrors},
+    include, networks, secrets,
+    services::{self, blkio_config, build, deploy, healthcheck, logging, ports, volumes},
+    span::{key_token, pointer},
+    volumes::Volume,
+    Compose,
+};
+
+/// How keys that are not part of the model are reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownKeys {
+    /// Drop unknown keys silently
+    #[default]
+    Ignore,
+    /// Report unknown keys as warnings, which do not prevent the manifest from being built
+    Warn,
+    /// Report unknown keys as errors
+    Deny,
+}
+
+/// Report the keys of `document` that are missing from `compose`, which was built from it
+pub(crate) fn check(
+    document: &Value,
+    compose: &Compose,
+    mode: UnknownKeys,
+    errors: &mut ValidationErrors,
+) {
+    let severity = match mode {
+        UnknownKeys::Ignore => return,
+        UnknownKeys::Warn => Severity::Warning,
+        UnknownKeys::Deny => Severity::Error,
+    };
+    let Ok(known) = serde_yaml::to_value(compose) else {
+        return;
+    };
+    let mut checker = Checker {
+        structs: structs(),
+        severity,
+        errors,
+    };
+    checker.walk(document, &known, "");
+}
+
+struct Checker<'a> {
+    structs: Vec<(&'static str, &'static [&'static str])>,
+    severity: Severity,
+    errors: &'a mut ValidationErrors,
+}
+
+impl Checker<'_> {
+    fn walk(&mut self, document: &Value, known: &Value, path: &str) {
+        match (document, known) {
+            (Value::Mapping(document), Value::Mapping(known)) => {
+                for (key, value) in document {
+                    let token = key_token(key);
+                    let child = pointer(path, &token);
+                    match known.iter().find(|(k, _)| key_token(k) == token) {
+                        Some((_, known)) => self.walk(value, known, &child),
+                        // Null values are indistinguishable from fields that are not set
+                        None if token.starts_with("x-") || value.is_null() => (),
+                        None => self.report(path, &child, &token),
+                    }
+                }
+            }
+            (Value::Sequence(document), Value::Sequence(known))
+                if document.len() == known.len() =>
+            {
+                for (index, (value, known)) in document.iter().zip(known).enumerate() {
+                    self.walk(value, known, &pointer(path, index));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn report(&mut self, parent: &str, path: &str, key: &str) {
+        let suggestion = self
+            .structs
+            .iter()
+            .find(|(pattern, _)| matches(pattern, parent))
+            .and_then(|(_, fields)| suggest(key, fields));
+        let message = match suggestion {
+            Some(field) => format!("Unknown key {key}, did you mean {field}?"),
+            None => format!("Unknown key {key}"),
+        };
+        self.errors.add_error(
+            ValidationError::new(ErrorCode::UnknownKey, path, message)
+                .with_value(key)
+                .with_severity(self.severity),
+        );
+    }
+}
+
+/// The field of `fields` nearest to `key`, if it is close enough to be a likely typo
+fn suggest(key: &str, fields: &[&'static str]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|field| (strsim::damerau_levenshtein(key, field), *field))
+        .filter(|(distance, _)| *distance <= (key.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field)
+}
+
+/// Whether `path` matches `pattern`, where a `*` token matches any key or index
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, path): (Vec<_>, Vec<_>) =
+        (pattern.split('/').collect(), path.split('/').collect());
+    pattern.len() == path.len()
+        && pattern
+            .iter()
+            .zip(&path)
+            .all(|(pattern, token)| *pattern == "*" || pattern == token)
+}
+
+/// The fields of every struct in the model, by the paths the struct appears at
+fn structs() -> Vec<(&'static str, &'static [&'static str])> {
+    vec![
+        ("", fields::<Compose>()),
+        ("/include/*", fields::<include::IncludeDetails>()),
+        ("/services/*", fields::<services::Service>()),
+        (
+            "/services/*/blkio_config",
+            fields::<blkio_config::BlkioConfig>(),
+        ),
+        (
+            "/services/*/blkio_config/weight_device/*",
+            fields::<blkio_config::WeightDevice>(),
+        ),
+        (
+            "/services/*/blkio_config/*/*",
+            fields::<blkio_config::DeviceReadBps>(),
+        ),
+        ("/services/*/build", fields::<build::BuildDetails>()),
+        (
+            "/services/*/build/secrets/*",
+            fields::<build::SecretDetails>(),
+        ),
+        ("/services/*/configs/*", fields::<services::ConfigDetails>()),
+        (
+            "/services/*/credential_spec",
+            fields::<services::CredentialSpec>(),
+        ),
+        (
+            "/services/*/depends_on/*",
+            fields::<services::DependsOnDetail>(),
+        ),
+        ("/services/*/deploy", fields::<deploy::Deploy>()),
+        (
+            "/services/*/deploy/placement",
+            fields::<deploy::Placement>(),
+        ),
+        (
+            "/services/*/deploy/resources",
+            fields::<deploy::Resources>(),
+        ),
+        (
+            "/services/*/deploy/resources/limits",
+            fields::<deploy::Limits>(),
+        ),
+        (
+            "/services/*/deploy/resources/reservations",
+            fields::<deploy::Reservations>(),
+        ),
+        (
+            "/services/*/deploy/restart_policy",
+            fields::<deploy::RestartPolicy>(),
+        ),
+        (
+            "/services/*/deploy/rollback_config",
+            fields::<deploy::RollbackConfig>(),
+        ),
+        (
+            "/services/*/deploy/update_config",
+            fields::<deploy::UpdateConfig>(),
+        ),
+        ("/services/*/extends", fields::<services::ExtendsDetails>()),
+        (
+            "/services/*/healthcheck",
+            fields::<healthcheck::HealthCheck>(),
+        ),
+        ("/services/*/logging", fields::<logging::Logging>()),
+        (
+            "/services/*/networks/*",
+            fields::<services::networks::NetworkOptions>(),
+        ),
+        ("/services/*/ports/*", fields::<ports::PortOptions>()),
+        (
+            "/services/*/secrets/*",
+            fields::<services::secrets::SecretOptions>(),
+        ),
+        ("/services/*/ulimits", fields::<services::Ulimits>()),
+        ("/services/*/ulimits/nofile", fields::<services::Nofile>()),
+        (
+            "/services/*/volumes/*",
+            fields::<volumes::LongVolumeOptions>(),
+        ),
+        ("/services/*/volumes/*/bind", fields::<volumes::Bind>()),
+        (
+            "/services/*/volumes/*/volume",
+            fields::<volumes::VolumeOptions>(),
+        ),
+        ("/services/*/volumes/*/tmpfs", fields::<volumes::Tmpfs>()),
+        ("/networks/*", fields::<networks::Network>()),
+        ("/networks/*/ipam", fields::<networks::Ipam>()),
+        ("/networks/*/ipam/config/*", fields::<networks::Config>()),
+        ("/volumes/*", fields::<Volume>()),
+        ("/configs/*", fields::<configs::Config>()),
+        ("/secrets/*", fields::<secrets::Secret>()),
+    ]
+}
+
+/// The field names of the struct `T`, as seen by its derived `Deserialize` implementation
+fn fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Probe<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for Probe<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("probed"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+            identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Probe(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+    services:
+      web:
+        image: web
+        enviroment:
+          LOG_LEVEL: debug
+        depend_on: [db]
+        secruity_opt: [no-new-privileges]
+        x-team: platform
+        deploy:
+          resources:
+            limit:
+              cpus: "0.5"
+              memory: 50M
+        ports:
+          - target: 80
+            publish: "8080"
+      db:
+        image: postgres
+        bogus: true
+    "#;
+
+    #[test]
+    fn test_fields() {
+        assert!(fields::<services::Service>().contains(&"security_opt"));
+        assert!(fields::<Compose>().contains(&"services"));
+        assert!(fields::<ports::PortOptions>().contains(&"published"));
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let errors = Compose::builder()
+            .document(YAML)
+            .unknown_keys(UnknownKeys::Deny)
+            .build()
+            .unwrap_err();
+        let reported: Vec<_> = errors
+            .with_code(ErrorCode::UnknownKey)
+            .map(|e| (e.path.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "/services/web/enviroment",
+                    "Unknown key enviroment, did you mean environment?"
+                ),
+                (
+                    "/services/web/depend_on",
+                    "Unknown key depend_on, did you mean depends_on?"
+                ),
+                (
+                    "/services/web/secruity_opt",
+                    "Unknown key secruity_opt, did you mean security_opt?"
+                ),
+                (
+                    "/services/web/deploy/resources/limit",
+                    "Unknown key limit, did you mean limits?"
+                ),
+                (
+                    "/services/web/ports/0/publish",
+                    "Unknown key publish, did you mean published?"
+                ),
+                ("/services/db/bogus", "Unknown key bogus"),
+            ]
+        );
+        let error = errors.with_code(ErrorCode::UnknownKey).next().unwrap();
+        assert_eq!(error.span.unwrap().start.line, 6);
+        #[cfg(not(feature = "compose-spec"))]
+        assert!(Compose::new(YAML).is_ok());
+    }
+
+    #[test]
+    // The schema rejects unknown keys on its own
+    #[cfg(not(feature = "compose-spec"))]
+    fn test_unknown_keys_as_warnings() {
+        let compose = Compose::builder()
+            .document(YAML)
+            .unknown_keys(UnknownKeys::Warn)
+            .build()
+            .unwrap();
+        assert_eq!(compose.warnings.len(), 6);
+        assert!(compose
+            .warnings
+            .iter()
+            .all(|w| w.severity == Severity::Warning && w.code == ErrorCode::UnknownKey));
+    }
+}
@@ -5,10 +5,13 @@ use serde::{Deserialize, Serialize};
 use crate::compose::Compose;
 use crate::compose::Validate;
 use crate::errors::ValidationErrors;
+use crate::extensions::Extensions;
 use crate::services::Labels;
 
 /// Represents the top level [Volume](https://docs.docker.com/compose/compose-file/07-volumes/) element
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct Volume {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub driver: Option<String>,
@@ -24,9 +27,15 @@ pub struct Volume {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+
+    #[serde(flatten, skip_deserializing)]
+    #[cfg_attr(feature = "typegen", schemars(skip), ts(skip))]
+    pub extensions: Extensions,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "typegen", derive(schemars::JsonSchema, ts_rs::TS))]
+#[cfg_attr(feature = "typegen", ts(optional_fields))]
 pub struct DriverOpts {
     #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
     pub driver_type: Option<String>,
@@ -39,10 +48,9 @@ pub struct DriverOpts {
 }
 
 impl Validate for Volume {
-    fn validate(&self, _: &Compose, _: &mut ValidationErrors) {
+    fn validate(&self, _: &Compose, _: &str, _: &mut ValidationErrors) {
         // Nothing to really validate here
         // Not too interested in validating the existence of drivers on the host
-        ()
     }
 }
 
@@ -57,7 +65,7 @@ mod tests {
             external: true
         "#;
         let volumes: Volume = serde_yaml::from_str(yaml).unwrap();
-        assert_eq!(volumes.external.unwrap(), true);
+        assert!(volumes.external.unwrap());
     }
 
     #[test]
@@ -75,20 +83,20 @@ mod tests {
 
         let volumes: Volume = serde_yaml::from_str(yaml).unwrap();
 
-        assert_eq!(volumes.driver.unwrap(), "driver2".to_string());
+        assert_eq!(volumes.driver.unwrap(), "driver2".to_owned());
         assert_eq!(
             volumes.driver_opts.as_ref().unwrap().driver_type,
-            Some("type1".to_string())
+            Some("type1".to_owned())
         );
         assert_eq!(
             volumes.driver_opts.as_ref().unwrap().o,
-            Some("option1".to_string())
+            Some("option1".to_owned())
         );
-        assert_eq!(volumes.external.unwrap(), true);
+        assert!(volumes.external.unwrap());
         if let Some(labels) = &volumes.labels {
             match labels {
                 Labels::List(list) => {
-                    assert_eq!(list, &vec!["label1".to_string(), "label2".to_string()]);
+                    assert_eq!(list, &vec!["label1".to_owned(), "label2".to_owned()]);
                     assert_eq!(list.len(), 2);
                 }
                 _ => panic!("Unexpected labels format"),